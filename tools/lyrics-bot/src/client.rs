@@ -0,0 +1,71 @@
+//! Abstraction over the GitHub operations the bot performs, so command
+//! logic in [`crate::commands`] can be unit tested without a network
+//! connection.
+
+use thiserror::Error;
+
+/// The pull request a bot command is being run against.
+#[derive(Debug, Clone)]
+pub struct PrContext {
+    pub owner: String,
+    pub repo: String,
+    pub number: u64,
+    pub head_branch: String,
+    pub commenter: String,
+}
+
+/// Errors surfaced while a bot command talks to GitHub or processes a
+/// submitted lyric file.
+#[derive(Debug, Error)]
+pub enum BotError {
+    #[error("GitHub API error: {0}")]
+    Api(String),
+    #[error(transparent)]
+    Convert(#[from] ttml_core::ConvertError),
+}
+
+/// Outcome of [`BotClient::rebase_onto_base`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebaseOutcome {
+    /// The branch already contained every commit from the base branch.
+    UpToDate,
+    /// The branch was brought up to date with the base branch and pushed.
+    Rebased,
+    /// Bringing the branch up to date hit a conflict that needs a human.
+    Conflicted,
+}
+
+/// Everything a bot command needs from GitHub. Implemented for real by a
+/// thin GitHub REST API client, and by an in-memory fake in tests.
+pub trait BotClient {
+    /// List the files changed in the PR as `(path, contents)` pairs. In
+    /// practice this is exactly one `lyrics/*.ttml` file.
+    fn list_files(&mut self, pr: &PrContext) -> Result<Vec<(String, String)>, BotError>;
+
+    /// Overwrite `path` on `pr.head_branch` with `contents`, force-pushing
+    /// a single commit (how `/retime` and `/metadata set` publish edits).
+    fn push_file(&mut self, pr: &PrContext, path: &str, contents: &str) -> Result<(), BotError>;
+
+    /// Post a comment on the PR.
+    fn post_comment(&mut self, pr: &PrContext, body: &str) -> Result<(), BotError>;
+
+    /// Whether `pr.commenter` may run bot commands on this PR (the PR
+    /// author or a repo maintainer).
+    fn verify_pr_permission(&mut self, pr: &PrContext) -> Result<bool, BotError>;
+
+    /// List every label defined on the repository (not just the ones
+    /// already on this PR), so `/label` can check a requested name is real
+    /// before applying it.
+    fn list_repo_labels(&mut self, pr: &PrContext) -> Result<Vec<String>, BotError>;
+
+    /// Add `labels` to the PR. Callers are expected to have already checked
+    /// each one against [`list_repo_labels`](Self::list_repo_labels).
+    fn add_labels_to_pr(&mut self, pr: &PrContext, labels: &[String]) -> Result<(), BotError>;
+
+    /// Bring `pr.head_branch` up to date with the repository's base branch
+    /// (rebasing, or merging if a rebase isn't possible) and push the
+    /// result, so a long-stale PR doesn't conflict with the base branch at
+    /// merge time. Must never force-resolve a conflict -- report
+    /// [`RebaseOutcome::Conflicted`] instead so a human handles it.
+    fn rebase_onto_base(&mut self, pr: &PrContext) -> Result<RebaseOutcome, BotError>;
+}
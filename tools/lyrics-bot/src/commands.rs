@@ -0,0 +1,952 @@
+//! PR comment command dispatch.
+
+use crate::client::{BotClient, BotError, PrContext, RebaseOutcome};
+use crate::verifier::{MetadataVerifier, NoopVerifier, VerificationOutcome};
+
+/// Matches `body` against `command` (e.g. `"/retime"`), requiring the
+/// character right after the prefix to be end-of-string or whitespace so a
+/// comment like `/labelled as done` isn't mistaken for `/label` with the
+/// garbage argument `"led as done"`. Returns the trimmed remainder on match.
+fn match_command<'a>(body: &'a str, command: &str) -> Option<&'a str> {
+    let rest = body.strip_prefix(command)?;
+    match rest.chars().next() {
+        None => Some(rest),
+        Some(c) if c.is_whitespace() => Some(rest.trim()),
+        _ => None,
+    }
+}
+
+/// Dispatch a PR comment `body` to the matching bot command, if any.
+/// Unrecognized comments are silently ignored.
+pub fn handle_command(
+    client: &mut dyn BotClient,
+    pr: &PrContext,
+    body: &str,
+) -> Result<(), BotError> {
+    let body = body.trim();
+    if let Some(rest) = match_command(body, "/retime") {
+        return handle_retime(client, pr, rest);
+    }
+    if let Some(rest) = match_command(body, "/preview") {
+        return handle_preview(client, pr, rest);
+    }
+    if let Some(rest) = match_command(body, "/label") {
+        return handle_label(client, pr, rest);
+    }
+    if match_command(body, "/rebase").is_some() {
+        return handle_rebase(client, pr);
+    }
+    if let Some(rest) = match_command(body, "/metadata") {
+        return handle_metadata(client, pr, rest);
+    }
+    Ok(())
+}
+
+/// Metadata keys a reviewer may fix via `/metadata set` without asking the
+/// submitter to re-upload. Deliberately narrower than every key this crate
+/// can parse -- fields like `ncmMusicId` identify *which* song this is, and
+/// editing them here would silently detach the submission from the platform
+/// entry it was verified against.
+const EDITABLE_METADATA_KEYS: &[&str] = &["musicName", "artists", "album"];
+
+/// `/metadata set <key> <value>` overwrites a single metadata value on the
+/// PR's TTML file and pushes the result, so a reviewer can fix a wrong
+/// title/artist/album without round-tripping through the submitter.
+fn handle_metadata(client: &mut dyn BotClient, pr: &PrContext, arg: &str) -> Result<(), BotError> {
+    if !client.verify_pr_permission(pr)? {
+        client.post_comment(
+            pr,
+            "You don't have permission to run bot commands on this PR.",
+        )?;
+        return Ok(());
+    }
+
+    let Some(rest) = arg.strip_prefix("set") else {
+        client.post_comment(
+            pr,
+            "`/metadata` currently only supports `/metadata set <key> <value>`.",
+        )?;
+        return Ok(());
+    };
+    let Some((key, value)) = rest.trim().split_once(char::is_whitespace) else {
+        client.post_comment(pr, "`/metadata set` expects a key and a value.")?;
+        return Ok(());
+    };
+    let key = key.trim();
+    let value = value.trim();
+
+    if !EDITABLE_METADATA_KEYS.contains(&key) {
+        client.post_comment(
+            pr,
+            &format!(
+                "`{key}` isn't editable via `/metadata set`. Editable keys: {}.",
+                EDITABLE_METADATA_KEYS.join(", ")
+            ),
+        )?;
+        return Ok(());
+    }
+
+    let files = client.list_files(pr)?;
+    let Some((path, contents)) = files.into_iter().find(|(p, _)| p.ends_with(".ttml")) else {
+        client.post_comment(pr, "No `.ttml` file found in this PR.")?;
+        return Ok(());
+    };
+
+    let mut data = ttml_core::parse_ttml(&contents)?;
+    let before = data
+        .metadata
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.clone());
+    data.metadata.retain(|(k, _)| k != key);
+    data.metadata.push((key.to_string(), value.to_string()));
+
+    let regenerated = ttml_core::generate_ttml_inner(
+        &data,
+        &ttml_core::TtmlGenerationOptions {
+            format: false,
+            ..Default::default()
+        },
+    )?;
+    client.push_file(pr, &path, &regenerated)?;
+    client.post_comment(
+        pr,
+        &format!(
+            "Set `{key}` to `{value}` (was {}).",
+            before
+                .map(|v| format!("`{v}`"))
+                .unwrap_or_else(|| "unset".to_string())
+        ),
+    )?;
+    Ok(())
+}
+
+/// `/rebase` brings a long-stale PR branch up to date with the base branch
+/// so it merges cleanly, without silently resolving a conflict itself.
+fn handle_rebase(client: &mut dyn BotClient, pr: &PrContext) -> Result<(), BotError> {
+    if !client.verify_pr_permission(pr)? {
+        client.post_comment(
+            pr,
+            "You don't have permission to run bot commands on this PR.",
+        )?;
+        return Ok(());
+    }
+
+    match client.rebase_onto_base(pr)? {
+        RebaseOutcome::UpToDate => {
+            client.post_comment(pr, "This branch is already up to date.")?;
+        }
+        RebaseOutcome::Rebased => {
+            client.post_comment(pr, "Rebased onto the base branch and pushed.")?;
+        }
+        RebaseOutcome::Conflicted => {
+            client.post_comment(
+                pr,
+                "Rebasing onto the base branch hit a conflict -- please resolve it manually.",
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn handle_preview(client: &mut dyn BotClient, pr: &PrContext, arg: &str) -> Result<(), BotError> {
+    const DEFAULT_LINE_COUNT: usize = 10;
+
+    let line_count = if arg.is_empty() {
+        DEFAULT_LINE_COUNT
+    } else {
+        match arg.parse() {
+            Ok(v) => v,
+            Err(_) => {
+                client.post_comment(
+                    pr,
+                    &format!("`/preview` expects an optional line count, got `{arg}`."),
+                )?;
+                return Ok(());
+            }
+        }
+    };
+
+    let files = client.list_files(pr)?;
+    let Some((_, contents)) = files.into_iter().find(|(p, _)| p.ends_with(".ttml")) else {
+        client.post_comment(pr, "No `.ttml` file found in this PR.")?;
+        return Ok(());
+    };
+
+    let data = ttml_core::parse_ttml(&contents)?;
+    if data.lines.is_empty() {
+        client.post_comment(pr, "This file has no lyric lines to preview.")?;
+        return Ok(());
+    }
+
+    let lrc = to_lrc_preview(&data, line_count);
+    client.post_comment(pr, &format!("```lrc\n{lrc}```\n"))
+}
+
+/// Render the first `line_count` lines as `[mm:ss.xx]text` LRC, joining
+/// each line's words as its main-track text.
+fn to_lrc_preview(data: &ttml_core::ParsedSourceData, line_count: usize) -> String {
+    let mut out = String::new();
+    for line in data.lines.iter().take(line_count) {
+        let text = line
+            .words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<String>();
+        let minutes = line.start_ms / 60_000;
+        let seconds = (line.start_ms % 60_000) / 1000;
+        let centiseconds = (line.start_ms % 1000) / 10;
+        out.push_str(&format!(
+            "[{minutes:02}:{seconds:02}.{centiseconds:02}]{text}\n"
+        ));
+    }
+    out
+}
+
+fn handle_retime(client: &mut dyn BotClient, pr: &PrContext, arg: &str) -> Result<(), BotError> {
+    if !client.verify_pr_permission(pr)? {
+        client.post_comment(
+            pr,
+            "You don't have permission to run bot commands on this PR.",
+        )?;
+        return Ok(());
+    }
+
+    let offset_ms: i64 = match arg.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            client.post_comment(
+                pr,
+                &format!("`/retime` expects a signed integer millisecond offset, got `{arg}`."),
+            )?;
+            return Ok(());
+        }
+    };
+
+    let files = client.list_files(pr)?;
+    let Some((path, contents)) = files.into_iter().find(|(p, _)| p.ends_with(".ttml")) else {
+        client.post_comment(pr, "No `.ttml` file found in this PR.")?;
+        return Ok(());
+    };
+
+    let mut data = ttml_core::parse_ttml(&contents)?;
+    apply_offset_ms(&mut data, offset_ms);
+    let regenerated = ttml_core::generate_ttml_inner(
+        &data,
+        &ttml_core::TtmlGenerationOptions {
+            format: false,
+            ..Default::default()
+        },
+    )?;
+
+    client.push_file(pr, &path, &regenerated)?;
+    client.post_comment(pr, &format!("Retimed all timestamps by {offset_ms}ms."))?;
+    Ok(())
+}
+
+/// `/label a, b c` applies `a`, `b`, and `c` to the PR, but only the ones
+/// that already exist as repo labels -- a typo'd name would otherwise get
+/// silently created by GitHub, which is worse than just rejecting it and
+/// suggesting the closest real one.
+fn handle_label(client: &mut dyn BotClient, pr: &PrContext, arg: &str) -> Result<(), BotError> {
+    if !client.verify_pr_permission(pr)? {
+        client.post_comment(
+            pr,
+            "You don't have permission to run bot commands on this PR.",
+        )?;
+        return Ok(());
+    }
+
+    let requested = parse_label_list(arg);
+    if requested.is_empty() {
+        client.post_comment(pr, "`/label` expects one or more label names.")?;
+        return Ok(());
+    }
+
+    let repo_labels = client.list_repo_labels(pr)?;
+    let mut applied = Vec::new();
+    let mut skipped = Vec::new();
+    for label in requested {
+        match repo_labels
+            .iter()
+            .find(|existing| existing.eq_ignore_ascii_case(&label))
+        {
+            Some(existing) => applied.push(existing.clone()),
+            None => {
+                let suggestion = repo_labels.iter().min_by_key(|candidate| {
+                    ttml_core::levenshtein_distance(
+                        &label.to_lowercase(),
+                        &candidate.to_lowercase(),
+                    )
+                });
+                skipped.push((label, suggestion.cloned()));
+            }
+        }
+    }
+
+    if !applied.is_empty() {
+        client.add_labels_to_pr(pr, &applied)?;
+    }
+
+    let mut body = String::new();
+    if !applied.is_empty() {
+        body.push_str(&format!("Applied: {}\n", applied.join(", ")));
+    }
+    if !skipped.is_empty() {
+        body.push_str("Skipped (not a repo label):\n");
+        for (label, suggestion) in &skipped {
+            match suggestion {
+                Some(closest) => {
+                    body.push_str(&format!("- `{label}` -- did you mean `{closest}`?\n"))
+                }
+                None => body.push_str(&format!("- `{label}`\n")),
+            }
+        }
+    }
+    client.post_comment(pr, &body)
+}
+
+/// Split a `/label` argument on commas and/or whitespace, so both
+/// `a, b, c` and `a b c` work as a label list.
+fn parse_label_list(arg: &str) -> Vec<String> {
+    arg.split(|c: char| c == ',' || c.is_whitespace())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Cap on parsed lyric lines a submission may contain before
+/// [`sync_metadata_to_issue_with_verifier`] declines it outright, used when
+/// `LYRICS_BOT_MAX_LINE_COUNT` isn't set. An accidental concatenation of a
+/// whole album into one file can run to tens of thousands of lines, which
+/// slows every later bot command and generation step down on a file no one
+/// intended to submit whole.
+const DEFAULT_MAX_LINE_COUNT: usize = 2000;
+
+/// Read the submission line-count limit from `LYRICS_BOT_MAX_LINE_COUNT`,
+/// falling back to [`DEFAULT_MAX_LINE_COUNT`] when it's unset or not a
+/// valid number.
+fn max_line_count() -> usize {
+    std::env::var("LYRICS_BOT_MAX_LINE_COUNT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_LINE_COUNT)
+}
+
+/// Post a comment listing the canonical metadata parsed from a submitted
+/// TTML file, along with a quick stats table (line/syllable counts,
+/// duration, and which auxiliary tracks are present), so the PR/issue's own
+/// description doesn't need to be trusted as a source of truth and a
+/// reviewer gets a quality signal before opening the diff.
+pub fn sync_metadata_to_issue(
+    client: &mut dyn BotClient,
+    pr: &PrContext,
+    data: &ttml_core::ParsedSourceData,
+) -> Result<(), BotError> {
+    sync_metadata_to_issue_with_verifier(client, pr, data, &mut NoopVerifier)
+}
+
+/// Like [`sync_metadata_to_issue`], but also cross-checks `data.metadata`
+/// against an external source of truth via `verifier`, appending a mismatch
+/// warning to the comment when it flags something. Pass [`NoopVerifier`]
+/// (what [`sync_metadata_to_issue`] does) when no external database is
+/// configured.
+pub fn sync_metadata_to_issue_with_verifier(
+    client: &mut dyn BotClient,
+    pr: &PrContext,
+    data: &ttml_core::ParsedSourceData,
+    verifier: &mut dyn MetadataVerifier,
+) -> Result<(), BotError> {
+    if data.metadata.is_empty() && data.lines.is_empty() {
+        return Ok(());
+    }
+
+    let max_lines = max_line_count();
+    if data.lines.len() > max_lines {
+        return client.post_comment(
+            pr,
+            &format!(
+                "This file has {} lyric lines, more than the {max_lines}-line limit -- \
+                 likely an accidental concatenation of multiple songs. Please split it \
+                 into separate submissions.",
+                data.lines.len()
+            ),
+        );
+    }
+
+    let mut body = String::new();
+    if !data.lines.is_empty() {
+        body.push_str(&build_stats_summary(data));
+        body.push('\n');
+    }
+    if !data.metadata.is_empty() {
+        body.push_str("已采用以下元数据：\n");
+        for (key, value) in &data.metadata {
+            body.push_str(&format!("- `{key}`: {value}\n"));
+        }
+        if let VerificationOutcome::Mismatched(reason) = verifier.verify(&data.metadata) {
+            body.push_str(&format!("\n⚠️ 元数据校验未通过：{reason}\n"));
+        }
+    }
+    client.post_comment(pr, &body)
+}
+
+/// Render a markdown table summarizing `data`: line/syllable counts, total
+/// duration, and whether translations/romanizations/background vocals are
+/// present, so a reviewer has a quick quality signal before opening the
+/// diff.
+fn build_stats_summary(data: &ttml_core::ParsedSourceData) -> String {
+    let total_duration_ms: u64 = data
+        .lines
+        .iter()
+        .map(ttml_core::LyricLine::duration_ms)
+        .sum();
+    let has_translations = data.lines.iter().any(|l| !l.translations.is_empty());
+    let has_romanizations = data.lines.iter().any(|l| !l.romanizations.is_empty());
+    let has_background = data
+        .lines
+        .iter()
+        .any(|l| l.content_type == ttml_core::ContentType::Background);
+
+    format!(
+        "| 指标 | 值 |\n\
+         | --- | --- |\n\
+         | 行数 | {} |\n\
+         | 音节数 | {} |\n\
+         | 总时长 | {}ms |\n\
+         | 翻译 | {} |\n\
+         | 音译 | {} |\n\
+         | 背景人声 | {} |\n",
+        data.lines.len(),
+        data.syllable_count(),
+        total_duration_ms,
+        yes_no(has_translations),
+        yes_no(has_romanizations),
+        yes_no(has_background),
+    )
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "有"
+    } else {
+        "无"
+    }
+}
+
+fn apply_offset_ms(data: &mut ttml_core::ParsedSourceData, offset_ms: i64) {
+    for line in &mut data.lines {
+        line.start_ms = shift(line.start_ms, offset_ms);
+        line.end_ms = shift(line.end_ms, offset_ms);
+        for word in &mut line.words {
+            word.start_ms = shift(word.start_ms, offset_ms);
+            word.end_ms = shift(word.end_ms, offset_ms);
+        }
+        for word in &mut line.background {
+            word.start_ms = shift(word.start_ms, offset_ms);
+            word.end_ms = shift(word.end_ms, offset_ms);
+        }
+        for annotated in line.translations.iter_mut().chain(&mut line.romanizations) {
+            for syllable in &mut annotated.syllables {
+                syllable.start_ms = shift(syllable.start_ms, offset_ms);
+                syllable.end_ms = shift(syllable.end_ms, offset_ms);
+            }
+        }
+    }
+}
+
+/// Apply a signed millisecond offset to a timestamp, saturating at 0
+/// instead of underflowing if the offset would push it negative.
+fn shift(ms: u64, offset_ms: i64) -> u64 {
+    (ms as i64 + offset_ms).max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct FakeClient {
+        permitted: bool,
+        files: Vec<(String, String)>,
+        repo_labels: Vec<String>,
+        rebase_outcome: Option<RebaseOutcome>,
+        pushed: RefCell<Vec<(String, String)>>,
+        comments: RefCell<Vec<String>>,
+        applied_labels: RefCell<Vec<String>>,
+    }
+
+    impl BotClient for FakeClient {
+        fn list_files(&mut self, _pr: &PrContext) -> Result<Vec<(String, String)>, BotError> {
+            Ok(self.files.clone())
+        }
+
+        fn push_file(
+            &mut self,
+            _pr: &PrContext,
+            path: &str,
+            contents: &str,
+        ) -> Result<(), BotError> {
+            self.pushed
+                .borrow_mut()
+                .push((path.to_string(), contents.to_string()));
+            Ok(())
+        }
+
+        fn post_comment(&mut self, _pr: &PrContext, body: &str) -> Result<(), BotError> {
+            self.comments.borrow_mut().push(body.to_string());
+            Ok(())
+        }
+
+        fn verify_pr_permission(&mut self, _pr: &PrContext) -> Result<bool, BotError> {
+            Ok(self.permitted)
+        }
+
+        fn list_repo_labels(&mut self, _pr: &PrContext) -> Result<Vec<String>, BotError> {
+            Ok(self.repo_labels.clone())
+        }
+
+        fn add_labels_to_pr(&mut self, _pr: &PrContext, labels: &[String]) -> Result<(), BotError> {
+            self.applied_labels
+                .borrow_mut()
+                .extend(labels.iter().cloned());
+            Ok(())
+        }
+
+        fn rebase_onto_base(&mut self, _pr: &PrContext) -> Result<RebaseOutcome, BotError> {
+            Ok(self.rebase_outcome.unwrap_or(RebaseOutcome::UpToDate))
+        }
+    }
+
+    fn pr() -> PrContext {
+        PrContext {
+            owner: "Steve-xmh".into(),
+            repo: "amll-ttml-db".into(),
+            number: 1,
+            head_branch: "contrib".into(),
+            commenter: "someone".into(),
+        }
+    }
+
+    const SAMPLE_TTML: &str = r#"<tt><body><div>
+        <p begin="00:00:01.000" end="00:00:02.000">
+            <span begin="00:00:01.000" end="00:00:02.000">hi</span>
+        </p>
+    </div></body></tt>"#;
+
+    #[test]
+    fn retime_shifts_all_timestamps_and_pushes() {
+        let mut client = FakeClient {
+            permitted: true,
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/retime 500").unwrap();
+
+        let pushed = client.pushed.borrow();
+        assert_eq!(pushed.len(), 1);
+        let data = ttml_core::parse_ttml(&pushed[0].1).unwrap();
+        assert_eq!(data.lines[0].start_ms, 1500);
+        assert_eq!(data.lines[0].end_ms, 2500);
+    }
+
+    #[test]
+    fn retime_saturates_at_zero_for_large_negative_offsets() {
+        let mut client = FakeClient {
+            permitted: true,
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/retime -5000").unwrap();
+
+        let pushed = client.pushed.borrow();
+        let data = ttml_core::parse_ttml(&pushed[0].1).unwrap();
+        assert_eq!(data.lines[0].start_ms, 0);
+        assert_eq!(data.lines[0].end_ms, 0);
+    }
+
+    #[test]
+    fn apply_offset_ms_shifts_background_and_word_timed_translation_syllables_too() {
+        let mut line = ttml_core::LyricLine::new(1000, 2000);
+        line.background.push(ttml_core::Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "oooh".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.translations.push(ttml_core::AnnotatedText {
+            lang: Some("zh".into()),
+            text: "你好".into(),
+            scheme: None,
+            syllables: vec![ttml_core::Word {
+                start_ms: 1000,
+                end_ms: 2000,
+                text: "你好".into(),
+                lang: None,
+                furigana: None,
+            }],
+        });
+        let mut data = ttml_core::ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+
+        apply_offset_ms(&mut data, 500);
+
+        assert_eq!(data.lines[0].background[0].start_ms, 1500);
+        assert_eq!(data.lines[0].background[0].end_ms, 2500);
+        let syllable = &data.lines[0].translations[0].syllables[0];
+        assert_eq!(syllable.start_ms, 1500);
+        assert_eq!(syllable.end_ms, 2500);
+    }
+
+    #[test]
+    fn retime_rejects_non_integer_argument() {
+        let mut client = FakeClient {
+            permitted: true,
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/retime soon").unwrap();
+
+        assert!(client.pushed.borrow().is_empty());
+        assert!(client.comments.borrow()[0].contains("signed integer"));
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_posts_a_summary_comment() {
+        let mut client = FakeClient::default();
+        let data = ttml_core::ParsedSourceData {
+            metadata: vec![("musicName".into(), "Groundless".into())],
+            ..Default::default()
+        };
+        sync_metadata_to_issue(&mut client, &pr(), &data).unwrap();
+        assert!(client.comments.borrow()[0].contains("musicName"));
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_with_verifier_appends_a_mismatch_warning() {
+        struct AlwaysMismatched;
+        impl MetadataVerifier for AlwaysMismatched {
+            fn verify(&mut self, _metadata: &[(String, String)]) -> VerificationOutcome {
+                VerificationOutcome::Mismatched("platform ID does not match title".into())
+            }
+        }
+
+        let mut client = FakeClient::default();
+        let data = ttml_core::ParsedSourceData {
+            metadata: vec![("ncmMusicId".into(), "12345".into())],
+            ..Default::default()
+        };
+        sync_metadata_to_issue_with_verifier(&mut client, &pr(), &data, &mut AlwaysMismatched)
+            .unwrap();
+        assert!(client.comments.borrow()[0].contains("platform ID does not match title"));
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_is_a_noop_without_metadata_or_lines() {
+        let mut client = FakeClient::default();
+        sync_metadata_to_issue(&mut client, &pr(), &ttml_core::ParsedSourceData::default())
+            .unwrap();
+        assert!(client.comments.borrow().is_empty());
+    }
+
+    fn lines_of(count: usize) -> Vec<ttml_core::LyricLine> {
+        (0..count)
+            .map(|i| {
+                let mut line = ttml_core::LyricLine::new(i as u64 * 1000, i as u64 * 1000 + 500);
+                line.words.push(ttml_core::Word {
+                    start_ms: line.start_ms,
+                    end_ms: line.end_ms,
+                    text: "la".into(),
+                    lang: None,
+                    furigana: None,
+                });
+                line
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_accepts_a_file_at_exactly_the_line_limit() {
+        std::env::remove_var("LYRICS_BOT_MAX_LINE_COUNT");
+        let mut client = FakeClient::default();
+        let data = ttml_core::ParsedSourceData {
+            lines: lines_of(DEFAULT_MAX_LINE_COUNT),
+            ..Default::default()
+        };
+        sync_metadata_to_issue(&mut client, &pr(), &data).unwrap();
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains("行数"));
+        assert!(!comment.contains("line limit"));
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_declines_a_file_one_line_over_the_limit() {
+        std::env::remove_var("LYRICS_BOT_MAX_LINE_COUNT");
+        let mut client = FakeClient::default();
+        let data = ttml_core::ParsedSourceData {
+            lines: lines_of(DEFAULT_MAX_LINE_COUNT + 1),
+            ..Default::default()
+        };
+        sync_metadata_to_issue(&mut client, &pr(), &data).unwrap();
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains("2001"));
+        assert!(comment.contains("2000-line limit"));
+    }
+
+    #[test]
+    fn sync_metadata_to_issue_includes_a_stats_table_when_lines_are_present() {
+        let mut client = FakeClient::default();
+        let data = ttml_core::parse_ttml(SAMPLE_TTML).unwrap();
+        sync_metadata_to_issue(&mut client, &pr(), &data).unwrap();
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains("行数 | 1"));
+        assert!(comment.contains("音节数 | 1"));
+        assert!(comment.contains("总时长 | 1000ms"));
+    }
+
+    #[test]
+    fn preview_renders_lrc_snippet() {
+        let mut client = FakeClient {
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/preview").unwrap();
+        assert!(client.comments.borrow()[0].contains("[00:01.00]hi"));
+    }
+
+    #[test]
+    fn preview_honors_line_count_argument() {
+        let two_lines = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">a</span>
+            </p>
+            <p begin="00:00:01.000" end="00:00:02.000">
+                <span begin="00:00:01.000" end="00:00:02.000">b</span>
+            </p>
+        </div></body></tt>"#;
+        let mut client = FakeClient {
+            files: vec![("lyrics/1.ttml".into(), two_lines.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/preview 1").unwrap();
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains('a'));
+        assert!(!comment.contains('b'));
+    }
+
+    #[test]
+    fn preview_rejects_non_numeric_line_count() {
+        let mut client = FakeClient::default();
+        handle_command(&mut client, &pr(), "/preview lots").unwrap();
+        assert!(client.comments.borrow()[0].contains("line count"));
+    }
+
+    #[test]
+    fn retime_is_rejected_without_permission() {
+        let mut client = FakeClient {
+            permitted: false,
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/retime 500").unwrap();
+
+        assert!(client.pushed.borrow().is_empty());
+        assert!(client.comments.borrow()[0].contains("permission"));
+    }
+
+    #[test]
+    fn label_applies_only_labels_that_exist_on_the_repo() {
+        let mut client = FakeClient {
+            permitted: true,
+            repo_labels: vec!["good first issue".into(), "needs-review".into()],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/label needs-review, typo-labl").unwrap();
+
+        assert_eq!(*client.applied_labels.borrow(), vec!["needs-review"]);
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains("Applied: needs-review"));
+        assert!(comment.contains("`typo-labl` -- did you mean `needs-review`?"));
+    }
+
+    #[test]
+    fn label_accepts_space_separated_names_too() {
+        let mut client = FakeClient {
+            permitted: true,
+            repo_labels: vec!["bug".into(), "enhancement".into()],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/label bug enhancement").unwrap();
+
+        let applied = client.applied_labels.borrow();
+        assert_eq!(applied.len(), 2);
+        assert!(applied.contains(&"bug".to_string()));
+        assert!(applied.contains(&"enhancement".to_string()));
+    }
+
+    #[test]
+    fn label_matches_repo_labels_case_insensitively() {
+        let mut client = FakeClient {
+            permitted: true,
+            repo_labels: vec!["Bug".into()],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/label bug").unwrap();
+        assert_eq!(*client.applied_labels.borrow(), vec!["Bug".to_string()]);
+    }
+
+    #[test]
+    fn label_is_rejected_without_permission() {
+        let mut client = FakeClient {
+            permitted: false,
+            repo_labels: vec!["bug".into()],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/label bug").unwrap();
+        assert!(client.applied_labels.borrow().is_empty());
+        assert!(client.comments.borrow()[0].contains("permission"));
+    }
+
+    #[test]
+    fn label_with_no_names_prompts_for_at_least_one() {
+        let mut client = FakeClient {
+            permitted: true,
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/label").unwrap();
+        assert!(client.comments.borrow()[0].contains("one or more label names"));
+    }
+
+    #[test]
+    fn a_word_that_merely_starts_with_a_command_name_is_not_dispatched() {
+        let mut client = FakeClient {
+            permitted: true,
+            repo_labels: vec!["bug".into()],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/labelled as done already").unwrap();
+        handle_command(&mut client, &pr(), "/retimed earlier please").unwrap();
+        handle_command(&mut client, &pr(), "/rebased onto the wrong branch").unwrap();
+        assert!(client.applied_labels.borrow().is_empty());
+        assert!(client.pushed.borrow().is_empty());
+        assert!(client.comments.borrow().is_empty());
+    }
+
+    #[test]
+    fn rebase_reports_when_already_up_to_date() {
+        let mut client = FakeClient {
+            permitted: true,
+            rebase_outcome: Some(RebaseOutcome::UpToDate),
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/rebase").unwrap();
+        assert!(client.comments.borrow()[0].contains("already up to date"));
+    }
+
+    #[test]
+    fn rebase_reports_success() {
+        let mut client = FakeClient {
+            permitted: true,
+            rebase_outcome: Some(RebaseOutcome::Rebased),
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/rebase").unwrap();
+        assert!(client.comments.borrow()[0].contains("Rebased onto the base branch"));
+    }
+
+    #[test]
+    fn rebase_asks_for_manual_help_on_conflict() {
+        let mut client = FakeClient {
+            permitted: true,
+            rebase_outcome: Some(RebaseOutcome::Conflicted),
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/rebase").unwrap();
+        assert!(client.comments.borrow()[0].contains("resolve it manually"));
+    }
+
+    #[test]
+    fn rebase_is_rejected_without_permission() {
+        let mut client = FakeClient {
+            permitted: false,
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/rebase").unwrap();
+        assert!(client.comments.borrow()[0].contains("permission"));
+    }
+
+    #[test]
+    fn metadata_set_overwrites_an_editable_key_and_reports_before_after() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:meta key="album" value="Old Album"/>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">hi</p>
+        </div></body></tt>"#;
+        let mut client = FakeClient {
+            permitted: true,
+            files: vec![("lyrics/1.ttml".into(), ttml.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/metadata set album New Album").unwrap();
+
+        let pushed = client.pushed.borrow();
+        let data = ttml_core::parse_ttml(&pushed[0].1).unwrap();
+        assert_eq!(
+            data.metadata
+                .iter()
+                .find(|(k, _)| k == "album")
+                .map(|(_, v)| v.as_str()),
+            Some("New Album")
+        );
+        let comment = &client.comments.borrow()[0];
+        assert!(comment.contains("Set `album` to `New Album`"));
+        assert!(comment.contains("was `Old Album`"));
+    }
+
+    #[test]
+    fn metadata_set_reports_unset_when_the_key_had_no_prior_value() {
+        let mut client = FakeClient {
+            permitted: true,
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/metadata set musicName Groundless").unwrap();
+        assert!(client.comments.borrow()[0].contains("was unset"));
+    }
+
+    #[test]
+    fn metadata_set_rejects_a_non_editable_key() {
+        let mut client = FakeClient {
+            permitted: true,
+            files: vec![("lyrics/1.ttml".into(), SAMPLE_TTML.into())],
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/metadata set ncmMusicId 12345").unwrap();
+        assert!(client.pushed.borrow().is_empty());
+        assert!(client.comments.borrow()[0].contains("isn't editable"));
+    }
+
+    #[test]
+    fn metadata_is_rejected_without_permission() {
+        let mut client = FakeClient {
+            permitted: false,
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/metadata set album X").unwrap();
+        assert!(client.comments.borrow()[0].contains("permission"));
+    }
+
+    #[test]
+    fn metadata_without_set_prompts_for_the_right_form() {
+        let mut client = FakeClient {
+            permitted: true,
+            ..Default::default()
+        };
+        handle_command(&mut client, &pr(), "/metadata album X").unwrap();
+        assert!(client.comments.borrow()[0].contains("/metadata set"));
+    }
+}
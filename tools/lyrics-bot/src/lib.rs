@@ -0,0 +1,9 @@
+//! PR comment command handling for the lyric-submission bot.
+
+mod client;
+mod commands;
+mod verifier;
+
+pub use client::{BotClient, BotError, PrContext, RebaseOutcome};
+pub use commands::{handle_command, sync_metadata_to_issue, sync_metadata_to_issue_with_verifier};
+pub use verifier::{MetadataVerifier, NoopVerifier, VerificationOutcome};
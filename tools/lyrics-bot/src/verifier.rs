@@ -0,0 +1,68 @@
+//! Optional cross-checking of a submission's metadata against an external
+//! source of truth (e.g. a music platform's own catalog), so a maintainer
+//! can catch a mistyped or mismatched platform ID before merging. The
+//! actual network lookup is entirely up to the caller; this crate only
+//! defines the interface and the no-op default used when nobody configures
+//! one.
+
+/// The result of checking one submission's metadata against an external
+/// database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationOutcome {
+    /// The metadata matches, or the verifier chose not to check it.
+    Consistent,
+    /// The metadata looks wrong; the string is a human-readable reason
+    /// suitable for posting straight into a PR comment.
+    Mismatched(String),
+}
+
+/// Cross-checks submitted lyric metadata (platform IDs, title, artists)
+/// against an external database. Implemented by callers that have access
+/// to that database; the crate itself only ships [`NoopVerifier`].
+pub trait MetadataVerifier {
+    fn verify(&mut self, metadata: &[(String, String)]) -> VerificationOutcome;
+}
+
+/// A [`MetadataVerifier`] that never flags anything, used when no external
+/// database is configured.
+#[derive(Debug, Default)]
+pub struct NoopVerifier;
+
+impl MetadataVerifier for NoopVerifier {
+    fn verify(&mut self, _metadata: &[(String, String)]) -> VerificationOutcome {
+        VerificationOutcome::Consistent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noop_verifier_is_always_consistent() {
+        let mut verifier = NoopVerifier;
+        let metadata = vec![("ncmMusicId".to_string(), "12345".to_string())];
+        assert_eq!(verifier.verify(&metadata), VerificationOutcome::Consistent);
+    }
+
+    struct MockVerifier {
+        outcome: VerificationOutcome,
+    }
+
+    impl MetadataVerifier for MockVerifier {
+        fn verify(&mut self, _metadata: &[(String, String)]) -> VerificationOutcome {
+            self.outcome.clone()
+        }
+    }
+
+    #[test]
+    fn mock_verifier_can_report_a_mismatch() {
+        let mut verifier = MockVerifier {
+            outcome: VerificationOutcome::Mismatched("title does not match platform".into()),
+        };
+        assert_eq!(
+            verifier.verify(&[]),
+            VerificationOutcome::Mismatched("title does not match platform".into())
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Content-hash cache for `--incremental` rebuilds.
+//!
+//! Every file's hash from the previous run is stored in
+//! `metadata/rebuild-cache.jsonl`, one JSON object per line, so the next run
+//! can tell which source files actually changed without re-hashing anything
+//! it doesn't have to keep in memory.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    file: String,
+    hash: u64,
+}
+
+/// Hash a source file's contents. Uses the standard library's `SipHash`
+/// rather than pulling in a dedicated hashing crate -- collisions would only
+/// cost an unnecessary rewrite, not correctness, so it doesn't need to be
+/// cryptographic.
+pub fn hash_contents(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Read `path` into a `file name -> hash` map, treating a missing or
+/// unreadable cache file as simply empty rather than an error -- the very
+/// first `--incremental` run has nothing to compare against yet.
+pub fn load_cache(path: &Path) -> HashMap<String, u64> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CacheEntry>(line).ok())
+        .map(|entry| (entry.file, entry.hash))
+        .collect()
+}
+
+/// Write `hashes` back out as JSON Lines, sorted by file name so the file is
+/// stable across runs and diffs cleanly.
+pub fn save_cache(path: &Path, hashes: &HashMap<String, u64>) -> std::io::Result<()> {
+    let mut files: Vec<&String> = hashes.keys().collect();
+    files.sort();
+
+    let mut out = String::new();
+    for file in files {
+        let entry = CacheEntry {
+            file: file.clone(),
+            hash: hashes[file],
+        };
+        out.push_str(&serde_json::to_string(&entry).expect("CacheEntry is plain data"));
+        out.push('\n');
+    }
+    std::fs::write(path, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_contents_hash_the_same() {
+        assert_eq!(hash_contents("abc"), hash_contents("abc"));
+    }
+
+    #[test]
+    fn different_contents_hash_differently() {
+        assert_ne!(hash_contents("abc"), hash_contents("abd"));
+    }
+
+    #[test]
+    fn load_cache_is_empty_when_the_file_is_missing() {
+        let cache = load_cache(Path::new("/nonexistent/rebuild-cache.jsonl"));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn save_then_load_round_trips_every_entry() {
+        let dir = std::env::temp_dir().join("rebuild_folder_cache_test_round_trip");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rebuild-cache.jsonl");
+
+        let mut hashes = HashMap::new();
+        hashes.insert("a.ttml".to_string(), 1);
+        hashes.insert("b.ttml".to_string(), 2);
+        save_cache(&path, &hashes).unwrap();
+
+        let loaded = load_cache(&path);
+        assert_eq!(loaded, hashes);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
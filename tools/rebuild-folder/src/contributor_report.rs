@@ -0,0 +1,170 @@
+//! Tallies how many lyric files each GitHub-identified contributor has
+//! authored, for `contributors.jsonl`/`CONTRIBUTORS.md` published alongside
+//! the built folder.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use ttml_core::ParsedSourceData;
+
+/// Metadata keys the submission bot writes onto a file's `<amll:meta>` when
+/// it merges the pull request that added it.
+const AUTHOR_ID_KEY: &str = "ttmlAuthorGithubId";
+const AUTHOR_LOGIN_KEY: &str = "ttmlAuthorGithubLogin";
+
+/// One contributor's row in the report: how many files they authored, and
+/// their GitHub login when one was recorded, so the website can render a
+/// name without a second API call to resolve the id.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContributorEntry {
+    #[serde(rename = "githubId")]
+    pub github_id: String,
+    #[serde(
+        rename = "ttmlAuthorGithubLogin",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub github_login: Option<String>,
+    pub count: usize,
+}
+
+/// Tally `entries` by author GitHub id, sorted by count descending, then by
+/// login (falling back to the id for a contributor with none on record) for
+/// a stable order when counts tie.
+pub fn generate_contributor_report(
+    entries: &[(String, ParsedSourceData)],
+) -> Vec<ContributorEntry> {
+    let mut by_id: HashMap<String, (usize, Option<String>)> = HashMap::new();
+    for (_, data) in entries {
+        let Some((_, id)) = data.metadata.iter().find(|(k, _)| k == AUTHOR_ID_KEY) else {
+            continue;
+        };
+        let login = data
+            .metadata
+            .iter()
+            .find(|(k, _)| k == AUTHOR_LOGIN_KEY)
+            .map(|(_, v)| v.clone());
+        let entry = by_id.entry(id.clone()).or_insert((0, None));
+        entry.0 += 1;
+        if entry.1.is_none() {
+            entry.1 = login;
+        }
+    }
+
+    let mut report: Vec<ContributorEntry> = by_id
+        .into_iter()
+        .map(|(github_id, (count, github_login))| ContributorEntry {
+            github_id,
+            github_login,
+            count,
+        })
+        .collect();
+    report.sort_by(|a, b| {
+        b.count.cmp(&a.count).then_with(|| {
+            let a_key = a.github_login.as_deref().unwrap_or(&a.github_id);
+            let b_key = b.github_login.as_deref().unwrap_or(&b.github_id);
+            a_key.cmp(b_key)
+        })
+    });
+    report
+}
+
+/// Render `report` as JSON Lines, one object per line, for writing to
+/// `contributors.jsonl`.
+pub fn contributor_report_to_jsonl(report: &[ContributorEntry]) -> String {
+    let mut out = String::new();
+    for entry in report {
+        out.push_str(&serde_json::to_string(entry).expect("ContributorEntry is plain data"));
+        out.push('\n');
+    }
+    out
+}
+
+/// Render `report` as the `CONTRIBUTORS.md` table, with each contributor's
+/// GitHub login in parentheses next to their id when one was resolved.
+pub fn contributor_report_to_markdown(report: &[ContributorEntry]) -> String {
+    let mut out = String::from("| Contributor | Files |\n| --- | --- |\n");
+    for entry in report {
+        let name = match &entry.github_login {
+            Some(login) => format!("{} ({login})", entry.github_id),
+            None => entry.github_id.clone(),
+        };
+        out.push_str(&format!("| {name} | {} |\n", entry.count));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_with_author(id: &str, login: Option<&str>) -> ParsedSourceData {
+        let mut metadata = vec![(AUTHOR_ID_KEY.to_string(), id.to_string())];
+        if let Some(login) = login {
+            metadata.push((AUTHOR_LOGIN_KEY.to_string(), login.to_string()));
+        }
+        ParsedSourceData {
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tallies_one_entry_per_distinct_author_id() {
+        let entries = vec![
+            ("1.ttml".to_string(), data_with_author("1", Some("alice"))),
+            ("2.ttml".to_string(), data_with_author("1", Some("alice"))),
+            ("3.ttml".to_string(), data_with_author("2", Some("bob"))),
+        ];
+        let report = generate_contributor_report(&entries);
+        assert_eq!(report.len(), 2);
+        assert_eq!(
+            report.iter().find(|r| r.github_id == "1").map(|r| r.count),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn files_with_no_author_metadata_are_ignored() {
+        let entries = vec![("1.ttml".to_string(), ParsedSourceData::default())];
+        assert!(generate_contributor_report(&entries).is_empty());
+    }
+
+    #[test]
+    fn report_is_sorted_by_count_descending_then_by_login() {
+        let entries = vec![
+            ("1.ttml".to_string(), data_with_author("1", Some("zed"))),
+            ("2.ttml".to_string(), data_with_author("2", Some("amy"))),
+            ("3.ttml".to_string(), data_with_author("2", Some("amy"))),
+        ];
+        let report = generate_contributor_report(&entries);
+        assert_eq!(report[0].github_id, "2");
+        assert_eq!(report[1].github_id, "1");
+    }
+
+    #[test]
+    fn a_missing_login_falls_back_to_the_github_id_for_sorting_and_display() {
+        let entries = vec![("1.ttml".to_string(), data_with_author("42", None))];
+        let report = generate_contributor_report(&entries);
+        assert_eq!(report[0].github_login, None);
+        assert!(contributor_report_to_markdown(&report).contains("| 42 |"));
+    }
+
+    #[test]
+    fn jsonl_output_has_one_object_per_contributor() {
+        let entries = vec![
+            ("1.ttml".to_string(), data_with_author("1", Some("alice"))),
+            ("2.ttml".to_string(), data_with_author("2", Some("bob"))),
+        ];
+        let report = generate_contributor_report(&entries);
+        let jsonl = contributor_report_to_jsonl(&report);
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(jsonl.contains("\"ttmlAuthorGithubLogin\":\"alice\""));
+    }
+
+    #[test]
+    fn markdown_output_shows_the_login_in_parentheses() {
+        let entries = vec![("1.ttml".to_string(), data_with_author("1", Some("alice")))];
+        let report = generate_contributor_report(&entries);
+        assert!(contributor_report_to_markdown(&report).contains("| 1 (alice) | 1 |"));
+    }
+}
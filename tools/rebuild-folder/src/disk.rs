@@ -0,0 +1,21 @@
+//! Writing derived lyric files alongside the source TTML.
+
+use std::path::Path;
+
+use ttml_core::ParsedSourceData;
+
+use crate::formats::{to_krc, to_lys, to_qrc};
+
+/// Write the QRC, KRC, and LYS renditions of `data` next to the source TTML
+/// file, using `stem` (the TTML file's name without extension) as the base
+/// name.
+pub fn save_lyric_files_to_disk(
+    dir: &Path,
+    stem: &str,
+    data: &ParsedSourceData,
+) -> std::io::Result<()> {
+    std::fs::write(dir.join(format!("{stem}.qrc")), to_qrc(data))?;
+    std::fs::write(dir.join(format!("{stem}.krc")), to_krc(data))?;
+    std::fs::write(dir.join(format!("{stem}.lys")), to_lys(data))?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Stringifiers that turn parsed TTML lyric data into the various
+//! line/word-timed text formats used by other lyric players.
+
+use std::fmt::Write as _;
+
+use ttml_core::ParsedSourceData;
+
+/// Render `data` as QQ Music's QRC format.
+///
+/// Each line is `[start,duration]word(word_offset,word_duration)...`, with
+/// word offsets/durations relative to the start of the recording.
+pub fn to_qrc(data: &ParsedSourceData) -> String {
+    let mut out = String::new();
+    out.push_str("[offset:0]\n");
+    for line in &data.lines {
+        let duration = line.end_ms.saturating_sub(line.start_ms);
+        write!(out, "[{},{}]", line.start_ms, duration).unwrap();
+        for word in &line.words {
+            let word_duration = word.end_ms.saturating_sub(word.start_ms);
+            write!(out, "{}({},{})", word.text, word.start_ms, word_duration).unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Render `data` as Kugou's KRC format.
+pub fn to_krc(data: &ParsedSourceData) -> String {
+    ttml_core::generate_krc(data)
+}
+
+/// Render `data` as AMLL's Lyricify Syllable (`.lys`) format.
+pub fn to_lys(data: &ParsedSourceData) -> String {
+    ttml_core::generate_lys(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ttml_core::{LyricLine, Word};
+
+    fn sample_data() -> ParsedSourceData {
+        let mut line = LyricLine::new(1000, 3000);
+        line.words.push(Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 2000,
+            end_ms: 3000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+        ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn qrc_uses_absolute_word_offsets() {
+        let qrc = to_qrc(&sample_data());
+        assert_eq!(
+            qrc,
+            "[offset:0]\n[1000,2000]hello(1000,1000)world(2000,1000)\n"
+        );
+    }
+
+    #[test]
+    fn krc_uses_line_relative_word_offsets() {
+        let krc = to_krc(&sample_data());
+        assert_eq!(
+            krc,
+            "[offset:0]\n[1000,2000]<0,1000,0>hello<1000,1000,0>world\n"
+        );
+    }
+
+    #[test]
+    fn krc_handles_line_without_word_timing() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "whole line".into(),
+            lang: None,
+            furigana: None,
+        });
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        let krc = to_krc(&data);
+        assert_eq!(krc, "[offset:0]\n[0,1000]<0,1000,0>whole line\n");
+    }
+
+    #[test]
+    fn lys_marks_a_line_with_no_agent_as_left_side_main_vocal() {
+        let lys = to_lys(&sample_data());
+        assert_eq!(lys, "[1]hello(1000,1000)world(2000,1000)\n");
+    }
+}
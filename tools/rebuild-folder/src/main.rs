@@ -0,0 +1,376 @@
+//! Rebuilds derived lyric formats (QRC, KRC, ...) for every TTML file in
+//! `lyrics/`.
+
+mod cache;
+mod contributor_report;
+mod disk;
+mod formats;
+mod perf;
+mod platform_index;
+mod song_metadata;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use cache::{hash_contents, load_cache, save_cache};
+use contributor_report::{
+    contributor_report_to_jsonl, contributor_report_to_markdown, generate_contributor_report,
+};
+use disk::save_lyric_files_to_disk;
+use perf::BuildPerfTimer;
+use platform_index::{build_platform_index, conflicts_to_jsonl, platform_counts};
+use song_metadata::write_song_metadata_files;
+
+/// `--lines-output <path>` from the command line, if given: where to write
+/// every parsed file's [`ttml_core::LyricLine`]s as pretty JSON, for
+/// downstream tooling that wants the structured line data without
+/// re-parsing every TTML file itself.
+fn lines_output_arg() -> Option<PathBuf> {
+    parse_lines_output_arg(std::env::args().skip(1))
+}
+
+fn parse_lines_output_arg(args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "--lines-output" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+/// `--dry-run` from the command line: perform parsing and platform-index
+/// building as usual, but skip writing derived files, `conflicts.jsonl`, and
+/// `--lines-output` to disk. For CI to validate the whole corpus without
+/// mutating the tree; exits nonzero if any file fails to parse.
+fn dry_run_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--dry-run")
+}
+
+/// `--per-song-metadata` from the command line: also write
+/// `metadata/<platform_key>/<id>.json` for every song, for a frontend detail
+/// page that wants one song's metadata without loading the aggregate index.
+/// Off by default since it writes one extra file per platform id.
+fn per_song_metadata_requested() -> bool {
+    std::env::args()
+        .skip(1)
+        .any(|arg| arg == "--per-song-metadata")
+}
+
+/// `--incremental` from the command line: skip rewriting a file's derived
+/// formats (and per-song metadata) when its content hash matches the
+/// previous run's, recorded in `metadata/rebuild-cache.jsonl`. Every file is
+/// still parsed and folded into the platform index and contributor report
+/// either way -- only the disk writes for unchanged files are skipped.
+fn incremental_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--incremental")
+}
+
+/// `--build-perf` from the command line: also write the per-stage timing
+/// report to `metadata/build-perf.json`, in addition to always printing it
+/// to stdout at the end of the run.
+fn build_perf_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--build-perf")
+}
+
+/// `--input <path>` from the command line: parse a single TTML file (or, with
+/// `-`, read TTML from stdin) and print its lines as JSON to stdout, instead
+/// of rebuilding the whole `lyrics/` directory. Lets the tool sit in a shell
+/// pipeline for a single file without touching `lyrics/` or `metadata/`.
+fn input_arg() -> Option<String> {
+    parse_input_arg(std::env::args().skip(1))
+}
+
+fn parse_input_arg(args: impl Iterator<Item = String>) -> Option<String> {
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "--input" {
+            return args.next();
+        }
+    }
+    None
+}
+
+/// Parse one TTML file (or stdin, given `-`) and print its lines as JSON,
+/// bypassing the directory-wide rebuild pipeline entirely.
+fn run_single_input(input: &str) -> std::io::Result<()> {
+    let source = if input == "-" {
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)?;
+        if buf.trim().is_empty() {
+            return Err(std::io::Error::other("no TTML received on stdin"));
+        }
+        buf
+    } else {
+        std::fs::read_to_string(input)?
+    };
+    let data = ttml_core::parse_ttml(&source).map_err(std::io::Error::other)?;
+    let json = serde_json::to_string_pretty(&data.lines).map_err(std::io::Error::other)?;
+    println!("{json}");
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    if let Some(input) = input_arg() {
+        return run_single_input(&input);
+    }
+
+    let lines_output = lines_output_arg();
+    let dry_run = dry_run_requested();
+    let per_song_metadata = per_song_metadata_requested();
+    let incremental = incremental_requested();
+    let build_perf = build_perf_requested();
+    let mut perf_timer = BuildPerfTimer::new();
+
+    let metadata_dir = Path::new("metadata");
+    let cache_path = metadata_dir.join("rebuild-cache.jsonl");
+    let previous_hashes = if incremental {
+        load_cache(&cache_path)
+    } else {
+        Default::default()
+    };
+    let mut current_hashes = HashMap::new();
+
+    let lyrics_dir = Path::new("lyrics");
+    let mut entries = Vec::new();
+    let mut all_lines = Vec::new();
+    let mut parse_failures = 0usize;
+    let mut skipped_unchanged = 0usize;
+    let mut parse_duration = std::time::Duration::ZERO;
+    let mut write_duration = std::time::Duration::ZERO;
+    for entry in std::fs::read_dir(lyrics_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("ttml") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let source = std::fs::read_to_string(&path)?;
+        let file_name = format!("{stem}.ttml");
+        let hash = hash_contents(&source);
+        let unchanged = incremental && previous_hashes.get(&file_name) == Some(&hash);
+        current_hashes.insert(file_name.clone(), hash);
+
+        let parse_start = std::time::Instant::now();
+        let parsed = ttml_core::parse_ttml(&source);
+        parse_duration += parse_start.elapsed();
+
+        match parsed {
+            Ok(data) => {
+                if dry_run {
+                    println!("would rebuild {stem}");
+                } else if unchanged {
+                    skipped_unchanged += 1;
+                    println!("unchanged {stem}");
+                } else {
+                    let write_start = std::time::Instant::now();
+                    save_lyric_files_to_disk(lyrics_dir, stem, &data)?;
+                    if per_song_metadata {
+                        write_song_metadata_files(metadata_dir, &data)?;
+                    }
+                    write_duration += write_start.elapsed();
+                    println!("rebuilt {stem}");
+                }
+                all_lines.extend(data.lines.clone());
+                entries.push((file_name, data));
+            }
+            Err(err) => {
+                parse_failures += 1;
+                eprintln!("failed to parse {}: {err}", path.display());
+            }
+        }
+    }
+    perf_timer.record("parse", parse_duration);
+    perf_timer.record("write", write_duration);
+
+    if incremental && !dry_run {
+        perf_timer.stage("cache", || save_cache(&cache_path, &current_hashes))?;
+        println!("incremental: skipped {skipped_unchanged} unchanged file(s)");
+    }
+
+    let (_, conflicts) = perf_timer.stage("platform_index", || build_platform_index(&entries));
+    for conflict in &conflicts {
+        eprintln!(
+            "warning: {} `{}` is claimed by both `{}` and `{}`; keeping `{}`",
+            conflict.platform_key,
+            conflict.id,
+            conflict.kept_file,
+            conflict.dropped_file,
+            conflict.kept_file
+        );
+    }
+
+    if dry_run {
+        println!("dry run: {} file(s) parsed successfully", entries.len());
+        for (key, count) in platform_counts(&entries) {
+            println!("  {key}: {count}");
+        }
+        print_and_maybe_write_perf_report(perf_timer, build_perf, metadata_dir)?;
+        if parse_failures > 0 {
+            eprintln!("dry run: {parse_failures} file(s) failed to parse");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if !conflicts.is_empty() {
+        std::fs::write(
+            metadata_dir.join("conflicts.jsonl"),
+            conflicts_to_jsonl(&conflicts),
+        )?;
+    }
+
+    let contributor_report = perf_timer.stage("contributor_report", || {
+        generate_contributor_report(&entries)
+    });
+    std::fs::write(
+        metadata_dir.join("contributors.jsonl"),
+        contributor_report_to_jsonl(&contributor_report),
+    )?;
+    std::fs::write(
+        "CONTRIBUTORS.md",
+        contributor_report_to_markdown(&contributor_report),
+    )?;
+
+    // Reflects the lines as they were right after parsing/validation, not
+    // after any of the derived-format generation above.
+    if let Some(path) = lines_output {
+        let json = serde_json::to_string_pretty(&all_lines).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)?;
+    }
+
+    print_and_maybe_write_perf_report(perf_timer, build_perf, metadata_dir)?;
+
+    Ok(())
+}
+
+/// Print the run's stage breakdown to stdout and, if `--build-perf` was
+/// given, also write it as JSON to `metadata/build-perf.json`.
+fn print_and_maybe_write_perf_report(
+    timer: BuildPerfTimer,
+    build_perf: bool,
+    metadata_dir: &Path,
+) -> std::io::Result<()> {
+    let report = timer.finish();
+    println!("build performance:");
+    println!("{}", report.summary());
+    if build_perf {
+        report.write_to(&metadata_dir.join("build-perf.json"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines_output_path_when_given() {
+        let args = ["--lines-output".to_string(), "out.json".to_string()].into_iter();
+        assert_eq!(
+            parse_lines_output_arg(args),
+            Some(PathBuf::from("out.json"))
+        );
+    }
+
+    #[test]
+    fn is_none_when_the_flag_is_absent() {
+        let args = ["--other-flag".to_string()].into_iter();
+        assert_eq!(parse_lines_output_arg(args), None);
+    }
+
+    #[test]
+    fn is_none_when_the_flag_is_missing_its_value() {
+        let args = ["--lines-output".to_string()].into_iter();
+        assert_eq!(parse_lines_output_arg(args), None);
+    }
+
+    fn parse_dry_run_flag(mut args: impl Iterator<Item = String>) -> bool {
+        args.any(|arg| arg == "--dry-run")
+    }
+
+    #[test]
+    fn dry_run_flag_is_detected_among_other_arguments() {
+        let args = [
+            "--lines-output".to_string(),
+            "out.json".to_string(),
+            "--dry-run".to_string(),
+        ]
+        .into_iter();
+        assert!(parse_dry_run_flag(args));
+    }
+
+    #[test]
+    fn dry_run_flag_is_false_when_absent() {
+        let args = ["--lines-output".to_string(), "out.json".to_string()].into_iter();
+        assert!(!parse_dry_run_flag(args));
+    }
+
+    fn parse_per_song_metadata_flag(mut args: impl Iterator<Item = String>) -> bool {
+        args.any(|arg| arg == "--per-song-metadata")
+    }
+
+    #[test]
+    fn per_song_metadata_flag_is_detected_among_other_arguments() {
+        let args = ["--dry-run".to_string(), "--per-song-metadata".to_string()].into_iter();
+        assert!(parse_per_song_metadata_flag(args));
+    }
+
+    #[test]
+    fn per_song_metadata_flag_is_false_when_absent() {
+        let args = ["--dry-run".to_string()].into_iter();
+        assert!(!parse_per_song_metadata_flag(args));
+    }
+
+    fn parse_incremental_flag(mut args: impl Iterator<Item = String>) -> bool {
+        args.any(|arg| arg == "--incremental")
+    }
+
+    #[test]
+    fn incremental_flag_is_detected_among_other_arguments() {
+        let args = ["--dry-run".to_string(), "--incremental".to_string()].into_iter();
+        assert!(parse_incremental_flag(args));
+    }
+
+    #[test]
+    fn incremental_flag_is_false_when_absent() {
+        let args = ["--dry-run".to_string()].into_iter();
+        assert!(!parse_incremental_flag(args));
+    }
+
+    fn parse_build_perf_flag(mut args: impl Iterator<Item = String>) -> bool {
+        args.any(|arg| arg == "--build-perf")
+    }
+
+    #[test]
+    fn build_perf_flag_is_detected_among_other_arguments() {
+        let args = ["--dry-run".to_string(), "--build-perf".to_string()].into_iter();
+        assert!(parse_build_perf_flag(args));
+    }
+
+    #[test]
+    fn build_perf_flag_is_false_when_absent() {
+        let args = ["--dry-run".to_string()].into_iter();
+        assert!(!parse_build_perf_flag(args));
+    }
+
+    #[test]
+    fn parses_input_path_when_given() {
+        let args = ["--input".to_string(), "song.ttml".to_string()].into_iter();
+        assert_eq!(parse_input_arg(args), Some("song.ttml".to_string()));
+    }
+
+    #[test]
+    fn input_is_none_when_the_flag_is_absent() {
+        let args = ["--dry-run".to_string()].into_iter();
+        assert_eq!(parse_input_arg(args), None);
+    }
+
+    #[test]
+    fn input_dash_is_kept_as_the_stdin_marker() {
+        let args = ["--input".to_string(), "-".to_string()].into_iter();
+        assert_eq!(parse_input_arg(args), Some("-".to_string()));
+    }
+}
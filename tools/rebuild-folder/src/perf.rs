@@ -0,0 +1,129 @@
+//! Per-stage timing for a rebuild run, printed as a summary and optionally
+//! written to `metadata/build-perf.json` (`--build-perf`) so a slow run in a
+//! large corpus can be pinned to parsing, disk writes, or platform-index
+//! building instead of just a single opaque total.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// One named stage's wall-clock duration, in the order it was recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub duration_ms: u128,
+}
+
+/// A completed rebuild run's stage breakdown, ready to print or serialize.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildPerfReport {
+    pub stages: Vec<StageTiming>,
+    pub total_ms: u128,
+}
+
+/// Accumulates stage timings as a run progresses via [`BuildPerfTimer::stage`].
+#[derive(Debug, Default)]
+pub struct BuildPerfTimer {
+    stages: Vec<StageTiming>,
+}
+
+impl BuildPerfTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time a single stage, recording its wall-clock duration under `name`
+    /// regardless of whether `f` returns an error.
+    pub fn stage<T>(&mut self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Record a stage's duration directly, for callers that measured it
+    /// themselves (e.g. accumulating time spent parsing across many
+    /// iterations of a loop that also does other, separately-timed work).
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        self.stages.push(StageTiming {
+            stage: name.to_string(),
+            duration_ms: duration.as_millis(),
+        });
+    }
+
+    /// Finish the run, producing a report whose total is the sum of every
+    /// recorded stage rather than a separately measured wall-clock total, so
+    /// the percentages a caller derives from it always add up to 100%.
+    #[must_use]
+    pub fn finish(self) -> BuildPerfReport {
+        let total_ms = self.stages.iter().map(|s| s.duration_ms).sum();
+        BuildPerfReport {
+            stages: self.stages,
+            total_ms,
+        }
+    }
+}
+
+impl BuildPerfReport {
+    /// Render the report as human-readable lines for stdout, one stage per
+    /// line with its share of the total.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        for stage in &self.stages {
+            let percent = if self.total_ms == 0 {
+                0.0
+            } else {
+                stage.duration_ms as f64 / self.total_ms as f64 * 100.0
+            };
+            out.push_str(&format!(
+                "  {}: {}ms ({percent:.1}%)\n",
+                stage.stage, stage.duration_ms
+            ));
+        }
+        out.push_str(&format!("  total: {}ms", self.total_ms));
+        out
+    }
+
+    /// Write the report as pretty JSON to `path`.
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("BuildPerfReport is plain data");
+        std::fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_sums_every_recorded_stage_as_the_total() {
+        let mut timer = BuildPerfTimer::new();
+        timer.stage("parse", || std::thread::sleep(Duration::from_millis(1)));
+        timer.stage("write", || std::thread::sleep(Duration::from_millis(1)));
+        let report = timer.finish();
+
+        assert_eq!(report.stages.len(), 2);
+        let summed: u128 = report.stages.iter().map(|s| s.duration_ms).sum();
+        assert_eq!(report.total_ms, summed);
+    }
+
+    #[test]
+    fn stage_returns_the_closures_value() {
+        let mut timer = BuildPerfTimer::new();
+        let value = timer.stage("compute", || 2 + 2);
+        assert_eq!(value, 4);
+    }
+
+    #[test]
+    fn summary_lists_every_stage_and_the_total() {
+        let mut timer = BuildPerfTimer::new();
+        timer.stage("parse", || {});
+        let report = timer.finish();
+
+        let summary = report.summary();
+        assert!(summary.contains("parse:"));
+        assert!(summary.contains("total:"));
+    }
+}
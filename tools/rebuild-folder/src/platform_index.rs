@@ -0,0 +1,175 @@
+//! Builds an index of parsed lyric files keyed by platform track id (e.g.
+//! `ncmMusicId`), so a collision -- two different files claiming the same
+//! id -- is caught and recorded instead of one silently overwriting the
+//! other.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use ttml_core::ParsedSourceData;
+
+/// Metadata keys treated as platform track ids, checked in this order.
+pub(crate) const PLATFORM_ID_KEYS: &[&str] =
+    &["ncmMusicId", "qqMusicId", "spotifyId", "appleMusicId"];
+
+/// Two files claiming the same `(platform_key, id)`; `kept_file` is the one
+/// the index retained.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlatformIdConflict {
+    pub platform_key: String,
+    pub id: String,
+    pub kept_file: String,
+    pub dropped_file: String,
+}
+
+/// The leading run of ASCII digits in a file name, used to prefer the file
+/// assumed newer (larger numeric prefix) when two collide.
+fn numeric_prefix(file_name: &str) -> u64 {
+    file_name
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .unwrap_or(0)
+}
+
+/// Build a `(platform_key, id) -> file_name` index across `entries`,
+/// preferring the entry with the larger numeric filename prefix on
+/// collision and recording every collision encountered.
+pub fn build_platform_index(
+    entries: &[(String, ParsedSourceData)],
+) -> (HashMap<(String, String), String>, Vec<PlatformIdConflict>) {
+    let mut index: HashMap<(String, String), String> = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for (file_name, data) in entries {
+        for key in PLATFORM_ID_KEYS {
+            let Some((_, id)) = data.metadata.iter().find(|(k, _)| k == key) else {
+                continue;
+            };
+            let index_key = (key.to_string(), id.clone());
+            match index.get(&index_key) {
+                Some(existing) if existing != file_name => {
+                    let (kept, dropped) = if numeric_prefix(file_name) > numeric_prefix(existing) {
+                        (file_name.clone(), existing.clone())
+                    } else {
+                        (existing.clone(), file_name.clone())
+                    };
+                    conflicts.push(PlatformIdConflict {
+                        platform_key: (*key).to_string(),
+                        id: id.clone(),
+                        kept_file: kept.clone(),
+                        dropped_file: dropped,
+                    });
+                    index.insert(index_key, kept);
+                }
+                _ => {
+                    index.insert(index_key, file_name.clone());
+                }
+            }
+        }
+    }
+
+    (index, conflicts)
+}
+
+/// Count how many `entries` carry each platform id key, for a quick summary
+/// of what a rebuild would touch without actually writing anything.
+pub fn platform_counts(entries: &[(String, ParsedSourceData)]) -> Vec<(&'static str, usize)> {
+    PLATFORM_ID_KEYS
+        .iter()
+        .map(|key| {
+            let count = entries
+                .iter()
+                .filter(|(_, data)| data.metadata.iter().any(|(k, _)| k == key))
+                .count();
+            (*key, count)
+        })
+        .collect()
+}
+
+/// Render `conflicts` as JSON Lines, one object per line, for writing to
+/// `conflicts.jsonl`.
+pub fn conflicts_to_jsonl(conflicts: &[PlatformIdConflict]) -> String {
+    let mut out = String::new();
+    for conflict in conflicts {
+        out.push_str(&serde_json::to_string(conflict).expect("PlatformIdConflict is plain data"));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_with_ncm_id(id: &str) -> ParsedSourceData {
+        ParsedSourceData {
+            metadata: vec![("ncmMusicId".into(), id.into())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn indexes_files_by_platform_id_without_conflicts() {
+        let entries = vec![
+            ("1.ttml".to_string(), data_with_ncm_id("111")),
+            ("2.ttml".to_string(), data_with_ncm_id("222")),
+        ];
+        let (index, conflicts) = build_platform_index(&entries);
+        assert_eq!(index.len(), 2);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn colliding_ncm_ids_keep_the_larger_numeric_prefix_and_report_the_conflict() {
+        let entries = vec![
+            ("100-old.ttml".to_string(), data_with_ncm_id("999")),
+            ("200-new.ttml".to_string(), data_with_ncm_id("999")),
+        ];
+        let (index, conflicts) = build_platform_index(&entries);
+        assert_eq!(
+            index[&("ncmMusicId".to_string(), "999".to_string())],
+            "200-new.ttml"
+        );
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kept_file, "200-new.ttml");
+        assert_eq!(conflicts[0].dropped_file, "100-old.ttml");
+    }
+
+    #[test]
+    fn platform_counts_tallies_each_key_independently() {
+        let entries = vec![
+            ("1.ttml".to_string(), data_with_ncm_id("111")),
+            ("2.ttml".to_string(), data_with_ncm_id("222")),
+        ];
+        let counts = platform_counts(&entries);
+        assert_eq!(
+            counts
+                .iter()
+                .find(|(key, _)| *key == "ncmMusicId")
+                .map(|(_, n)| *n),
+            Some(2)
+        );
+        assert_eq!(
+            counts
+                .iter()
+                .find(|(key, _)| *key == "qqMusicId")
+                .map(|(_, n)| *n),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn conflicts_to_jsonl_emits_one_object_per_line() {
+        let conflicts = vec![PlatformIdConflict {
+            platform_key: "ncmMusicId".into(),
+            id: "999".into(),
+            kept_file: "200-new.ttml".into(),
+            dropped_file: "100-old.ttml".into(),
+        }];
+        let jsonl = conflicts_to_jsonl(&conflicts);
+        assert_eq!(jsonl.lines().count(), 1);
+        assert!(jsonl.contains("\"ncmMusicId\""));
+    }
+}
@@ -0,0 +1,84 @@
+//! Optional per-song metadata JSON files, for a frontend detail page that
+//! wants a single song's public metadata by platform id without re-parsing
+//! every lyric file.
+
+use std::path::Path;
+
+use serde::Serialize;
+use ttml_core::ParsedSourceData;
+
+use crate::platform_index::PLATFORM_ID_KEYS;
+
+/// The full public metadata for one song, written to
+/// `metadata/<platform_key>/<id>.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SongMetadata<'a> {
+    pub metadata: &'a [(String, String)],
+    pub formats: &'a [&'static str],
+}
+
+/// Formats [`save_lyric_files_to_disk`](crate::disk::save_lyric_files_to_disk)
+/// always produces alongside the source TTML.
+const AVAILABLE_FORMATS: &[&str] = &["ttml", "qrc", "krc", "lys"];
+
+/// Write `metadata/<platform_key>/<id>.json` for every platform id `data`
+/// carries, overwriting any existing file for that id. Writes one extra file
+/// per platform id, which is why this is opt-in via `--per-song-metadata`
+/// rather than always on.
+pub fn write_song_metadata_files(
+    metadata_dir: &Path,
+    data: &ParsedSourceData,
+) -> std::io::Result<()> {
+    for key in PLATFORM_ID_KEYS {
+        let Some((_, id)) = data.metadata.iter().find(|(k, _)| k == key) else {
+            continue;
+        };
+        let dir = metadata_dir.join(key);
+        std::fs::create_dir_all(&dir)?;
+        let song_metadata = SongMetadata {
+            metadata: &data.metadata,
+            formats: AVAILABLE_FORMATS,
+        };
+        let json = serde_json::to_string_pretty(&song_metadata).map_err(std::io::Error::other)?;
+        std::fs::write(dir.join(format!("{id}.json")), json)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_one_json_file_per_platform_id_present() {
+        let dir = std::env::temp_dir().join(format!(
+            "amll-song-metadata-test-{:?}",
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let data = ParsedSourceData {
+            metadata: vec![
+                ("ncmMusicId".into(), "111".into()),
+                ("spotifyId".into(), "abc".into()),
+            ],
+            ..Default::default()
+        };
+        write_song_metadata_files(&dir, &data).unwrap();
+        assert!(dir.join("ncmMusicId").join("111.json").is_file());
+        assert!(dir.join("spotifyId").join("abc.json").is_file());
+        assert!(!dir.join("qqMusicId").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn is_a_noop_when_no_platform_id_is_present() {
+        let dir = std::env::temp_dir().join(format!(
+            "amll-song-metadata-test-empty-{:?}",
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let data = ParsedSourceData::default();
+        write_song_metadata_files(&dir, &data).unwrap();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,93 @@
+use thiserror::Error;
+
+/// Errors that can occur while parsing or generating TTML lyric documents.
+///
+/// [`std::fmt::Display`] (and thus `{err}`/`.to_string()`) always renders the
+/// English message; use [`ConvertError::describe`] when a caller (e.g. the
+/// bot posting a PR comment) needs the message in a specific [`Locale`].
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error("failed to parse TTML XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("malformed TTML: {0}")]
+    Malformed(String),
+    #[error("invalid timestamp `{0}`")]
+    InvalidTimestamp(String),
+    #[error("failed to serialize to JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("cannot align furigana: {0}")]
+    FuriganaAlignment(String),
+}
+
+/// A language to render a [`ConvertError`] in via [`ConvertError::describe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Zh,
+}
+
+impl ConvertError {
+    /// Render this error's user-facing message in `locale`, for callers
+    /// (like the review bot) that surface errors to a specific audience
+    /// rather than a developer log.
+    #[must_use]
+    pub fn describe(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (Self::Xml(inner), Locale::En) => format!("failed to parse TTML XML: {inner}"),
+            (Self::Xml(inner), Locale::Zh) => format!("TTML XML 解析失败：{inner}"),
+            (Self::Malformed(detail), Locale::En) => format!("malformed TTML: {detail}"),
+            (Self::Malformed(detail), Locale::Zh) => format!("TTML 格式错误：{detail}"),
+            (Self::InvalidTimestamp(value), Locale::En) => format!("invalid timestamp `{value}`"),
+            (Self::InvalidTimestamp(value), Locale::Zh) => format!("时间戳无效：`{value}`"),
+            (Self::Json(inner), Locale::En) => format!("failed to serialize to JSON: {inner}"),
+            (Self::Json(inner), Locale::Zh) => format!("序列化为 JSON 失败：{inner}"),
+            (Self::FuriganaAlignment(detail), Locale::En) => {
+                format!("cannot align furigana: {detail}")
+            }
+            (Self::FuriganaAlignment(detail), Locale::Zh) => {
+                format!("无法对齐注音：{detail}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_renders_english_by_default_locale() {
+        let err = ConvertError::InvalidTimestamp("12x".into());
+        assert_eq!(err.describe(Locale::default()), "invalid timestamp `12x`");
+    }
+
+    #[test]
+    fn describe_renders_chinese_when_requested() {
+        let err = ConvertError::InvalidTimestamp("12x".into());
+        assert_eq!(err.describe(Locale::Zh), "时间戳无效：`12x`");
+    }
+
+    #[test]
+    fn describe_covers_malformed_in_both_locales() {
+        let err = ConvertError::Malformed("missing <body>".into());
+        assert_eq!(err.describe(Locale::En), "malformed TTML: missing <body>");
+        assert_eq!(err.describe(Locale::Zh), "TTML 格式错误：missing <body>");
+    }
+
+    #[test]
+    fn describe_covers_furigana_alignment_in_both_locales() {
+        let err = ConvertError::FuriganaAlignment("length mismatch".into());
+        assert_eq!(
+            err.describe(Locale::En),
+            "cannot align furigana: length mismatch"
+        );
+        assert_eq!(err.describe(Locale::Zh), "无法对齐注音：length mismatch");
+    }
+
+    #[test]
+    fn display_is_unaffected_by_locale_and_always_english() {
+        let err = ConvertError::Malformed("missing <body>".into());
+        assert_eq!(err.to_string(), err.describe(Locale::En));
+    }
+}
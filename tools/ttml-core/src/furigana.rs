@@ -0,0 +1,102 @@
+//! Alignment of a Japanese main track against a kana/romaji reading track
+//! into per-word furigana, for callers that have both tracks as separately
+//! transcribed [`Word`] sequences (e.g. a kanji lyric line and a matching
+//! hiragana one) and want [`Word::furigana`] populated so the generator can
+//! emit `<ruby>`/`<rt>`.
+//!
+//! Only equal-length, one-to-one alignment is supported: the two tracks must
+//! have already been split on the same word boundaries. Lyric tracks
+//! transcribed independently rarely agree on where those boundaries fall,
+//! and guessing at a many-to-one alignment would silently produce wrong
+//! readings more often than it would help, so a length mismatch is reported
+//! as [`ConvertError::FuriganaAlignment`] instead.
+
+use crate::errors::ConvertError;
+use crate::types::{FuriganaSyllable, Word};
+
+/// Align `main` (kanji-bearing) words against `reading` (kana or romanized)
+/// words one-to-one, returning a copy of `main` with [`Word::furigana`] set
+/// from the corresponding `reading` word's text.
+///
+/// # Errors
+///
+/// Returns [`ConvertError::FuriganaAlignment`] if the two tracks don't have
+/// the same number of words.
+pub fn align_furigana(main: &[Word], reading: &[Word]) -> Result<Vec<Word>, ConvertError> {
+    if main.len() != reading.len() {
+        return Err(ConvertError::FuriganaAlignment(format!(
+            "main track has {} word(s) but reading track has {} -- equal-length alignment needs a 1:1 match",
+            main.len(),
+            reading.len()
+        )));
+    }
+    Ok(main
+        .iter()
+        .zip(reading.iter())
+        .map(|(word, reading_word)| {
+            let mut aligned = word.clone();
+            aligned.furigana = Some(vec![FuriganaSyllable {
+                base: word.text.clone(),
+                reading: reading_word.text.clone(),
+            }]);
+            aligned
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Word {
+        Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: text.into(),
+            lang: None,
+            furigana: None,
+        }
+    }
+
+    #[test]
+    fn equal_length_tracks_align_word_for_word() {
+        let main = vec![word("漢字"), word("読み")];
+        let reading = vec![word("かんじ"), word("よみ")];
+
+        let aligned = align_furigana(&main, &reading).unwrap();
+
+        assert_eq!(aligned.len(), 2);
+        let furigana = aligned[0].furigana.as_ref().unwrap();
+        assert_eq!(furigana.len(), 1);
+        assert_eq!(furigana[0].base, "漢字");
+        assert_eq!(furigana[0].reading, "かんじ");
+        assert_eq!(aligned[1].furigana.as_ref().unwrap()[0].reading, "よみ");
+    }
+
+    #[test]
+    fn mismatched_lengths_are_reported_rather_than_guessed_at() {
+        let main = vec![word("漢字")];
+        let reading = vec![word("かん"), word("じ")];
+
+        let err = align_furigana(&main, &reading).unwrap_err();
+
+        assert!(matches!(err, ConvertError::FuriganaAlignment(_)));
+    }
+
+    #[test]
+    fn alignment_preserves_the_main_track_timing() {
+        let main = vec![Word {
+            start_ms: 500,
+            end_ms: 900,
+            text: "漢字".into(),
+            lang: None,
+            furigana: None,
+        }];
+        let reading = vec![word("かんじ")];
+
+        let aligned = align_furigana(&main, &reading).unwrap();
+
+        assert_eq!(aligned[0].start_ms, 500);
+        assert_eq!(aligned[0].end_ms, 900);
+    }
+}
@@ -0,0 +1,1485 @@
+//! Generation of Apple Music / AMLL compatible TTML documents from
+//! [`ParsedSourceData`].
+
+use std::fmt::Write as _;
+
+use crate::errors::ConvertError;
+use crate::types::{AgentType, ParsedSourceData, Word};
+
+/// Which Unicode normalization form
+/// [`TtmlGenerationOptions::normalize_unicode`] applies to syllable,
+/// translation, and romanization text before writing it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: combine a base character and its combining
+    /// marks into their precomposed form (e.g. `e` + combining acute ->
+    /// `é`), without changing which characters are considered equivalent.
+    Nfc,
+    /// Compatibility composition: like `Nfc`, but also folds compatibility
+    /// variants together (e.g. full-width `Ａ` -> ASCII `A`), for input
+    /// methods that produce visually-identical but codepoint-different
+    /// text.
+    Nfkc,
+}
+
+/// The indentation unit used when [`TtmlGenerationOptions::format`] is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// `n` spaces per indent level.
+    Spaces(usize),
+    /// A single tab per indent level.
+    Tabs,
+}
+
+impl IndentStyle {
+    fn unit(&self) -> String {
+        match self {
+            IndentStyle::Spaces(n) => " ".repeat(*n),
+            IndentStyle::Tabs => "\t".to_string(),
+        }
+    }
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        IndentStyle::Spaces(2)
+    }
+}
+
+/// Controls how [`generate_ttml_inner`] groups lines into `<div>` elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivStrategy {
+    /// Start a new `<div>` whenever a line's div-level song-part
+    /// (`SongPart::div`) changes, tagging each `<div>` with
+    /// `itunes:song-part` when it has one. A line whose own `SongPart::p`
+    /// overrides the enclosing div's part is given its own
+    /// `itunes:song-part` on the `<p>` instead of starting a new `<div>`,
+    /// so a per-`<p>` override round-trips as a `<p>`-level override rather
+    /// than being promoted into div-level grouping. This is the default:
+    /// it's the only strategy that round-trips a parsed document's
+    /// song-part boundaries.
+    #[default]
+    PerSongPart,
+    /// Put every line under a single `<div>`, ignoring the div-level
+    /// `SongPart::div`. A line's own `SongPart::p` is still written as
+    /// `itunes:song-part` on its `<p>`. Useful for exporters that don't
+    /// care about part boundaries and want the flattest possible layout.
+    SingleDiv,
+    /// Give every line its own `<div>`.
+    PerLine,
+}
+
+/// Controls where [`generate_ttml_inner`] writes a line's
+/// [`LyricLine::translations`](crate::LyricLine::translations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranslationLayout {
+    /// Only as head-level `<amll:translation for="...">` elements, mirroring
+    /// how the parser primarily reads translations back. This is the
+    /// default.
+    #[default]
+    Head,
+    /// Only as an inline `<span ttm:role="x-translation">` on each line.
+    Inline,
+    /// Both at once, for compatibility with players that only recognize one
+    /// of the two layouts. A line that lacks its own `itunes:key` is given
+    /// a synthesized `L<n>` one so the head-level element has something to
+    /// reference. On the way back in, the parser dedupes an inline
+    /// translation against its head-level counterpart, so this doesn't
+    /// double a line's `translations` on round-trip.
+    Both,
+}
+
+/// Precision [`format_ttml_time`] writes a timestamp with, controlled by
+/// [`TtmlGenerationOptions::time_precision`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimePrecision {
+    /// `hh:mm:ss.mmm`, to the millisecond. This is the default, and what
+    /// this crate's parser has always read back.
+    #[default]
+    Milliseconds,
+    /// `hh:mm:ss.cc`, to the hundredth of a second, rounding to the nearest
+    /// centisecond. Matches how some of Apple's own TTML exports are
+    /// authored.
+    Centiseconds,
+}
+
+/// Options controlling how [`generate_ttml_inner`] renders a document.
+#[derive(Debug, Clone, Default)]
+pub struct TtmlGenerationOptions {
+    /// Emit `itunes:timing="word"/"line"` in lowercase instead of the
+    /// default `"Word"`/`"Line"`. Some parsers (including some historical
+    /// versions of this crate) only recognize the lowercase form.
+    pub itunes_timing_lowercase: bool,
+    /// Pretty-print with newlines and indentation (`true`, what
+    /// [`generate_ttml`] uses) or emit a single compact line (`false`, the
+    /// derived `Default`), e.g. for the bot's force-push diffs where a
+    /// minimal diff matters more than readability.
+    pub format: bool,
+    /// Indentation unit used when `format` is set.
+    pub indent: IndentStyle,
+    /// Bake the `offset` metadata value (if present) into every emitted
+    /// time and omit it from `<head>`, instead of leaving it for the player
+    /// to apply. Useful for exporting a "flattened" TTML to players that
+    /// don't support the `offset` convention.
+    pub apply_offset: bool,
+    /// How to group lines into `<div>` elements.
+    pub div_strategy: DivStrategy,
+    /// Emit each line's stored [`LyricLine::key`] as its `itunes:key`
+    /// attribute, instead of omitting it. Off by default since most callers
+    /// never look at a line's key once it's parsed; turn this on when
+    /// round-tripping a document that an external file (e.g. a translation
+    /// pack) references by its original `itunes:key`s, so those references
+    /// don't go stale.
+    pub preserve_itunes_keys: bool,
+    /// Where to write each line's translations.
+    pub translation_layout: TranslationLayout,
+    /// Merge a word whose `start_ms == end_ms` into the following word's
+    /// text instead of writing it as its own degenerate zero-length
+    /// `<span>`. A zero-width word with nothing after it (the last word of
+    /// a line) is dropped entirely rather than merged backwards, since a
+    /// trailing span's timing can't absorb it without shifting that span's
+    /// own end. Off by default, since it changes the line's word count.
+    pub collapse_zero_width_syllables: bool,
+    /// Normalize syllable, translation, and romanization text to this
+    /// Unicode normalization form before writing it out, so lyrics typed
+    /// with differently-composed combining characters (e.g. combining-mark
+    /// pinyin) or full-width/half-width variants compare and render
+    /// consistently across players. Requires this crate's
+    /// `unicode-normalize` feature -- `Some` without it fails generation
+    /// with [`ConvertError::Malformed`] rather than silently skipping the
+    /// normalization. `None` (the default) leaves text untouched.
+    pub normalize_unicode: Option<NormalizationForm>,
+    /// Strip stray control characters -- C0/C1 controls other than tab and
+    /// newline, plus the U+2028 line separator and U+2029 paragraph
+    /// separator -- from syllable, translation, and romanization text
+    /// before writing it out. [`escape_text`] only escapes the characters
+    /// XML itself requires; U+2028/U+2029 and other control characters pass
+    /// straight through and have been observed to break some players. Off
+    /// by default, since it's an extra pass over every syllable's text.
+    pub sanitize_control_chars: bool,
+    /// Precision every timestamp is written with.
+    pub time_precision: TimePrecision,
+}
+
+/// Generate a pretty-printed TTML document from `data`: the default options
+/// with `format` set to `true`.
+pub fn generate_ttml(data: &ParsedSourceData) -> Result<String, ConvertError> {
+    generate_ttml_inner(
+        data,
+        &TtmlGenerationOptions {
+            format: true,
+            ..TtmlGenerationOptions::default()
+        },
+    )
+}
+
+/// Rough number of bytes a `<span begin=... end=...>text</span>` plus its
+/// surrounding indentation/newline costs, used to pre-size the output
+/// buffer and avoid repeated reallocation on large files.
+const BYTES_PER_SYLLABLE_ESTIMATE: usize = 64;
+
+/// Fixed overhead for the document's header, `<head>`/`<body>` scaffolding,
+/// and closing tags, independent of how many lines it has.
+const BASE_OUTPUT_CAPACITY: usize = 512;
+
+/// Estimate how many bytes [`generate_ttml_inner`]'s output will need, so
+/// its `String` can be allocated once with [`String::with_capacity`]
+/// instead of growing repeatedly as syllables are written.
+fn estimated_output_capacity(data: &ParsedSourceData) -> usize {
+    BASE_OUTPUT_CAPACITY + data.syllable_count() * BYTES_PER_SYLLABLE_ESTIMATE
+}
+
+/// Group `metadata` by key, in the order each key first appears, sorting
+/// the values within each group. `metadata` allows the same key more than
+/// once (e.g. several `artists` entries), and without this the emitted
+/// order of those values would just follow whatever order they happened to
+/// sit in the source `Vec` -- meaning two otherwise-equivalent documents
+/// could produce different `<amll:meta>` output depending on how their
+/// metadata was collected, which breaks diffing and caching that assumes
+/// generation is deterministic.
+fn stable_metadata_groups(metadata: &[(String, String)]) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for (key, value) in metadata {
+        match groups
+            .iter_mut()
+            .find(|(existing_key, _)| existing_key == key)
+        {
+            Some((_, values)) => values.push(value.clone()),
+            None => groups.push((key.clone(), vec![value.clone()])),
+        }
+    }
+    for (_, values) in &mut groups {
+        values.sort();
+    }
+    groups
+}
+
+/// Apply `form` to a single piece of text.
+#[cfg(feature = "unicode-normalize")]
+fn normalize_text(s: &str, form: NormalizationForm) -> String {
+    use unicode_normalization::UnicodeNormalization;
+    match form {
+        NormalizationForm::Nfc => s.nfc().collect(),
+        NormalizationForm::Nfkc => s.nfkc().collect(),
+    }
+}
+
+/// Apply `form` to a single word's text and any furigana readings it
+/// carries.
+#[cfg(feature = "unicode-normalize")]
+fn normalize_word(word: &mut Word, form: NormalizationForm) {
+    word.text = normalize_text(&word.text, form);
+    if let Some(furigana) = &mut word.furigana {
+        for syllable in furigana {
+            syllable.base = normalize_text(&syllable.base, form);
+            syllable.reading = normalize_text(&syllable.reading, form);
+        }
+    }
+}
+
+/// Clone `data` with every syllable/translation/romanization text run
+/// through [`NormalizationForm`] `form`, for [`TtmlGenerationOptions::normalize_unicode`].
+#[cfg(feature = "unicode-normalize")]
+fn normalize_source_data(
+    data: &ParsedSourceData,
+    form: NormalizationForm,
+) -> Result<ParsedSourceData, ConvertError> {
+    let mut data = data.clone();
+    for line in &mut data.lines {
+        for word in &mut line.words {
+            normalize_word(word, form);
+        }
+        for word in &mut line.background {
+            normalize_word(word, form);
+        }
+        for annotated in line.translations.iter_mut().chain(&mut line.romanizations) {
+            annotated.text = normalize_text(&annotated.text, form);
+            for syllable in &mut annotated.syllables {
+                normalize_word(syllable, form);
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// `unicode-normalize` was requested but this build was compiled without
+/// the feature that provides it.
+#[cfg(not(feature = "unicode-normalize"))]
+fn normalize_source_data(
+    _data: &ParsedSourceData,
+    _form: NormalizationForm,
+) -> Result<ParsedSourceData, ConvertError> {
+    Err(ConvertError::Malformed(
+        "normalize_unicode was requested, but this build was compiled without the \
+         `unicode-normalize` feature"
+            .to_string(),
+    ))
+}
+
+/// Whether `c` is one of the stray control characters
+/// [`TtmlGenerationOptions::sanitize_control_chars`] strips: a C0/C1 control
+/// other than tab or newline, or the U+2028/U+2029 line/paragraph
+/// separators.
+fn is_stray_control_char(c: char) -> bool {
+    matches!(c,
+        '\u{0000}'..='\u{0008}'
+        | '\u{000B}'..='\u{001F}'
+        | '\u{007F}'..='\u{009F}'
+        | '\u{2028}'
+        | '\u{2029}'
+    )
+}
+
+/// Strip [`is_stray_control_char`] characters out of a single piece of text.
+fn sanitize_text(s: &str) -> String {
+    s.chars().filter(|c| !is_stray_control_char(*c)).collect()
+}
+
+/// Apply [`sanitize_text`] to a single word's text and any furigana readings
+/// it carries.
+fn sanitize_word(word: &mut Word) {
+    word.text = sanitize_text(&word.text);
+    if let Some(furigana) = &mut word.furigana {
+        for syllable in furigana {
+            syllable.base = sanitize_text(&syllable.base);
+            syllable.reading = sanitize_text(&syllable.reading);
+        }
+    }
+}
+
+/// Clone `data` with every syllable/translation/romanization text run
+/// through [`sanitize_text`], for
+/// [`TtmlGenerationOptions::sanitize_control_chars`].
+fn sanitize_source_data(data: &ParsedSourceData) -> ParsedSourceData {
+    let mut data = data.clone();
+    for line in &mut data.lines {
+        for word in &mut line.words {
+            sanitize_word(word);
+        }
+        for word in &mut line.background {
+            sanitize_word(word);
+        }
+        for annotated in line.translations.iter_mut().chain(&mut line.romanizations) {
+            annotated.text = sanitize_text(&annotated.text);
+            for syllable in &mut annotated.syllables {
+                sanitize_word(syllable);
+            }
+        }
+    }
+    data
+}
+
+/// Generate a TTML document from `data`, honoring `options`.
+pub fn generate_ttml_inner(
+    data: &ParsedSourceData,
+    options: &TtmlGenerationOptions,
+) -> Result<String, ConvertError> {
+    let normalized_data;
+    let data: &ParsedSourceData = match options.normalize_unicode {
+        Some(form) => {
+            normalized_data = normalize_source_data(data, form)?;
+            &normalized_data
+        }
+        None => data,
+    };
+    let sanitized_data;
+    let data: &ParsedSourceData = if options.sanitize_control_chars {
+        sanitized_data = sanitize_source_data(data);
+        &sanitized_data
+    } else {
+        data
+    };
+    let has_word_timing = data.lines.iter().any(|line| line.words.len() > 1);
+    let timing = if has_word_timing { "Word" } else { "Line" };
+    let timing = if options.itunes_timing_lowercase {
+        timing.to_ascii_lowercase()
+    } else {
+        timing.to_string()
+    };
+
+    let offset_ms: i64 = if options.apply_offset {
+        data.metadata
+            .iter()
+            .find(|(key, _)| key == "offset")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let shifted = |ms: u64| -> u64 { (ms as i64 + offset_ms).max(0) as u64 };
+
+    let dur_ms = data
+        .lines
+        .iter()
+        .map(|l| shifted(l.end_ms))
+        .max()
+        .unwrap_or(0);
+
+    let nl = if options.format { "\n" } else { "" };
+    let unit = options.indent.unit();
+    let ind = |depth: usize| -> String {
+        if options.format {
+            unit.repeat(depth)
+        } else {
+            String::new()
+        }
+    };
+
+    let writes_head_translations = matches!(
+        options.translation_layout,
+        TranslationLayout::Head | TranslationLayout::Both
+    );
+    let writes_inline_translations = matches!(
+        options.translation_layout,
+        TranslationLayout::Inline | TranslationLayout::Both
+    );
+    // A line's own `key` is used to reference it from a head-level
+    // `<amll:translation for="...">` when present; one is synthesized
+    // (matching the crate's own `L<n>` convention) for lines that don't
+    // have one but still need to be referenced.
+    let effective_keys: Vec<String> = data
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| line.key.clone().unwrap_or_else(|| format!("L{}", i + 1)))
+        .collect();
+
+    let mut out = String::with_capacity(estimated_output_capacity(data));
+    write!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>{nl}").unwrap();
+    write!(
+        out,
+        "<tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:ttm=\"http://www.w3.org/ns/ttml#metadata\" xmlns:itunes=\"http://music.apple.com/lyric-ttml-internal\" xmlns:amll=\"http://www.example.com/ns/amll\" itunes:timing=\"{timing}\">{nl}"
+    )
+    .unwrap();
+
+    write!(out, "{}<head>{nl}{}<metadata>{nl}", ind(1), ind(2)).unwrap();
+    for agent in &data.agents {
+        // A `Some("")` or whitespace-only name carries no information, so
+        // treat it the same as `None` and fall back to the self-closing
+        // form rather than emitting an empty `<ttm:name></ttm:name>`.
+        let name = agent
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+        let type_attr = match agent.agent_type {
+            AgentType::Person => "person",
+            AgentType::Group => "group",
+        };
+        match name {
+            Some(name) => write!(
+                out,
+                "{}<ttm:agent type=\"{type_attr}\" xml:id=\"{}\"><ttm:name>{}</ttm:name></ttm:agent>{nl}",
+                ind(3),
+                escape_attr(&agent.id),
+                escape_text(name)
+            )
+            .unwrap(),
+            None => write!(
+                out,
+                "{}<ttm:agent type=\"{type_attr}\" xml:id=\"{}\"/>{nl}",
+                ind(3),
+                escape_attr(&agent.id)
+            )
+            .unwrap(),
+        }
+    }
+    for (key, values) in stable_metadata_groups(&data.metadata) {
+        if options.apply_offset && key == "offset" {
+            continue;
+        }
+        for value in values {
+            write!(
+                out,
+                "{}<amll:meta key=\"{}\" value=\"{}\"/>{nl}",
+                ind(3),
+                escape_attr(&key),
+                escape_attr(&value)
+            )
+            .unwrap();
+        }
+    }
+    for (qname, value) in &data.raw_metadata {
+        write!(
+            out,
+            "{}<{qname}>{}</{qname}>{nl}",
+            ind(3),
+            escape_text(value)
+        )
+        .unwrap();
+    }
+    if writes_head_translations {
+        for (line, key) in data.lines.iter().zip(&effective_keys) {
+            for translation in &line.translations {
+                let lang_attr = translation
+                    .lang
+                    .as_deref()
+                    .map(|lang| format!(" lang=\"{}\"", escape_attr(lang)))
+                    .unwrap_or_default();
+                write!(
+                    out,
+                    "{}<amll:translation for=\"{}\"{lang_attr}>{}</amll:translation>{nl}",
+                    ind(3),
+                    escape_attr(key),
+                    escape_text(&translation.text)
+                )
+                .unwrap();
+            }
+        }
+    }
+    write!(out, "{}</metadata>{nl}{}</head>{nl}", ind(2), ind(1)).unwrap();
+
+    write!(
+        out,
+        "{}<body dur=\"{}\">{nl}",
+        ind(1),
+        format_ttml_time(dur_ms, options.time_precision)
+    )
+    .unwrap();
+    let mut line_index = 0usize;
+    for group in group_lines_for_divs(&data.lines, options.div_strategy) {
+        let song_part_attr = group
+            .part
+            .map(|part| format!(" itunes:song-part=\"{}\"", escape_attr(part)))
+            .unwrap_or_default();
+        write!(out, "{}<div{song_part_attr}>{nl}", ind(2)).unwrap();
+        for line in group.lines {
+            let agent_attr = line
+                .agent
+                .as_ref()
+                .map(|a| format!(" ttm:agent=\"{}\"", escape_attr(a)))
+                .unwrap_or_default();
+            // A line referenced by a head-level translation needs its key
+            // written even if `preserve_itunes_keys` is off, or the
+            // `<amll:translation for="...">` element would point at
+            // nothing.
+            let needs_head_key = writes_head_translations && !line.translations.is_empty();
+            let key_attr = if needs_head_key {
+                format!(
+                    " itunes:key=\"{}\"",
+                    escape_attr(&effective_keys[line_index])
+                )
+            } else if options.preserve_itunes_keys {
+                line.key
+                    .as_ref()
+                    .map(|key| format!(" itunes:key=\"{}\"", escape_attr(key)))
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+            // `region` is only ever an opaque reference into a `<head>`
+            // layout tree this crate doesn't parse or generate, so it's
+            // round-tripped as-is rather than validated.
+            let region_attr = line
+                .region
+                .as_ref()
+                .map(|region| format!(" region=\"{}\"", escape_attr(region)))
+                .unwrap_or_default();
+            // A `SongPart::p` that differs from the enclosing div's part is
+            // a per-`<p>` override and is written here rather than being
+            // folded into `group.part` above, so it round-trips as an
+            // override instead of promoting the whole group to a new `<div>`.
+            let p_song_part_attr = match line.song_part.p.as_deref() {
+                Some(p) if Some(p) != group.part => {
+                    format!(" itunes:song-part=\"{}\"", escape_attr(p))
+                }
+                _ => String::new(),
+            };
+            line_index += 1;
+            write!(
+                out,
+                "{}<p begin=\"{}\" end=\"{}\"{}{}{}{}>{nl}",
+                ind(3),
+                format_ttml_time(shifted(line.start_ms), options.time_precision),
+                format_ttml_time(shifted(line.end_ms), options.time_precision),
+                agent_attr,
+                key_attr,
+                region_attr,
+                p_song_part_attr
+            )
+            .unwrap();
+            // A line with only one "word" and no syllable-level `xml:lang`
+            // carries no useful per-syllable data of its own -- it's really
+            // line-timed data sharing a document with word-timed neighbors
+            // (a verse sung line by line, a chorus sung word by word) -- so
+            // its text is written directly rather than wrapped in a single
+            // redundant `<span>`. The document's `itunes:timing` attribute
+            // still reflects whether *any* line is word-timed, since that's
+            // what tells a player whether to look for per-word spans at all.
+            let owned_words;
+            let words: &[Word] = if options.collapse_zero_width_syllables {
+                owned_words = collapse_zero_width_syllables(&line.words);
+                &owned_words
+            } else {
+                &line.words
+            };
+            let needs_span_wrapping =
+                words.len() > 1 || words.first().is_some_and(|w| w.lang.is_some());
+            if needs_span_wrapping {
+                for word in words {
+                    let lang_attr = word
+                        .lang
+                        .as_deref()
+                        .map(|lang| format!(" xml:lang=\"{}\"", escape_attr(lang)))
+                        .unwrap_or_default();
+                    write!(
+                        out,
+                        "{}<span begin=\"{}\" end=\"{}\"{lang_attr}>{}</span>{nl}",
+                        ind(4),
+                        format_ttml_time(shifted(word.start_ms), options.time_precision),
+                        format_ttml_time(shifted(word.end_ms), options.time_precision),
+                        word_body(word)
+                    )
+                    .unwrap();
+                }
+            } else if let Some(word) = words.first() {
+                write!(out, "{}{}{nl}", ind(4), word_body(word)).unwrap();
+            }
+            for romanization in &line.romanizations {
+                let scheme_attr = romanization
+                    .scheme
+                    .as_deref()
+                    .map(|scheme| format!(" xml:scheme=\"{}\"", escape_attr(scheme)))
+                    .unwrap_or_default();
+                write!(
+                    out,
+                    "{}<span ttm:role=\"x-roman\"{scheme_attr}>{}</span>{nl}",
+                    ind(4),
+                    escape_text(&romanization.text)
+                )
+                .unwrap();
+            }
+            if writes_inline_translations {
+                for translation in &line.translations {
+                    let lang_attr = translation
+                        .lang
+                        .as_deref()
+                        .map(|lang| format!(" xml:lang=\"{}\"", escape_attr(lang)))
+                        .unwrap_or_default();
+                    if translation.syllables.is_empty() {
+                        write!(
+                            out,
+                            "{}<span ttm:role=\"x-translation\"{lang_attr}>{}</span>{nl}",
+                            ind(4),
+                            escape_text(&translation.text)
+                        )
+                        .unwrap();
+                    } else {
+                        // A word-timed translation nests one `<span
+                        // begin=... end=...>` per syllable, the same shape
+                        // the parser reassembles back into `syllables`.
+                        write!(
+                            out,
+                            "{}<span ttm:role=\"x-translation\"{lang_attr}>{nl}",
+                            ind(4)
+                        )
+                        .unwrap();
+                        for syllable in &translation.syllables {
+                            write!(
+                                out,
+                                "{}<span begin=\"{}\" end=\"{}\">{}</span>{nl}",
+                                ind(5),
+                                format_ttml_time(
+                                    shifted(syllable.start_ms),
+                                    options.time_precision
+                                ),
+                                format_ttml_time(shifted(syllable.end_ms), options.time_precision),
+                                escape_text(&syllable.text)
+                            )
+                            .unwrap();
+                        }
+                        write!(out, "{}</span>{nl}", ind(4)).unwrap();
+                    }
+                }
+            }
+            if !line.background.is_empty() {
+                // Background vocals aren't given per-word spans of their own
+                // here; the line's own begin/end is enough to round-trip a
+                // line-timed "(oooh)" ad-lib without inventing timing we
+                // never parsed.
+                let joined = line
+                    .background
+                    .iter()
+                    .map(|word| word.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                write!(
+                    out,
+                    "{}<span ttm:role=\"x-bg\" begin=\"{}\" end=\"{}\">{}</span>{nl}",
+                    ind(4),
+                    format_ttml_time(shifted(line.start_ms), options.time_precision),
+                    format_ttml_time(shifted(line.end_ms), options.time_precision),
+                    escape_text(&joined)
+                )
+                .unwrap();
+            }
+            write!(out, "{}</p>{nl}", ind(3)).unwrap();
+        }
+        write!(out, "{}</div>{nl}", ind(2)).unwrap();
+    }
+    write!(out, "{}</body>{nl}</tt>{nl}", ind(1)).unwrap();
+
+    Ok(out)
+}
+
+/// One `<div>` worth of lines, with the `itunes:song-part` (if any) that
+/// should be written on it.
+struct DivGroup<'a> {
+    part: Option<&'a str>,
+    lines: Vec<&'a crate::types::LyricLine>,
+}
+
+/// Group `lines` into the `<div>`s [`generate_ttml_inner`] should emit,
+/// according to `strategy`.
+fn group_lines_for_divs(
+    lines: &[crate::types::LyricLine],
+    strategy: DivStrategy,
+) -> Vec<DivGroup<'_>> {
+    match strategy {
+        DivStrategy::SingleDiv => vec![DivGroup {
+            part: None,
+            lines: lines.iter().collect(),
+        }],
+        DivStrategy::PerLine => lines
+            .iter()
+            .map(|line| DivGroup {
+                part: None,
+                lines: vec![line],
+            })
+            .collect(),
+        DivStrategy::PerSongPart => {
+            // Grouped by the div-level part only -- a `SongPart::p` override
+            // is written on the `<p>` itself (see the per-line loop above)
+            // rather than folded in here, so it doesn't spuriously start a
+            // new `<div>`.
+            let mut groups: Vec<DivGroup<'_>> = Vec::new();
+            for line in lines {
+                let part = line.song_part.div.as_deref();
+                match groups.last_mut() {
+                    Some(group) if group.part == part => group.lines.push(line),
+                    _ => groups.push(DivGroup {
+                        part,
+                        lines: vec![line],
+                    }),
+                }
+            }
+            groups
+        }
+    }
+}
+
+/// Format a millisecond timestamp as a TTML clock-time value at `precision`,
+/// rounding to the nearest unit `precision` writes (e.g. `123.4`ms rounds to
+/// the nearest centisecond as `.12` under [`TimePrecision::Centiseconds`]).
+pub fn format_ttml_time(ms: u64, precision: TimePrecision) -> String {
+    match precision {
+        TimePrecision::Milliseconds => {
+            let hours = ms / 3_600_000;
+            let minutes = (ms % 3_600_000) / 60_000;
+            let seconds = (ms % 60_000) / 1000;
+            let millis = ms % 1000;
+            format!("{hours:02}:{minutes:02}:{seconds:02}.{millis:03}")
+        }
+        TimePrecision::Centiseconds => {
+            let centiseconds = (ms as f64 / 10.0).round() as u64;
+            let hours = centiseconds / 360_000;
+            let minutes = (centiseconds % 360_000) / 6_000;
+            let seconds = (centiseconds % 6_000) / 100;
+            let centis = centiseconds % 100;
+            format!("{hours:02}:{minutes:02}:{seconds:02}.{centis:02}")
+        }
+    }
+}
+
+fn escape_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attr(s: &str) -> String {
+    escape_text(s).replace('"', "&quot;")
+}
+
+/// The text a word contributes inside its `<span>` (or bare inside `<p>`):
+/// plain escaped text, or one `<ruby>...<rt>...</rt></ruby>` per furigana
+/// syllable set on it by [`crate::align_furigana`].
+fn word_body(word: &crate::types::Word) -> String {
+    match &word.furigana {
+        Some(syllables) if !syllables.is_empty() => syllables
+            .iter()
+            .map(|syllable| {
+                format!(
+                    "<ruby>{}<rt>{}</rt></ruby>",
+                    escape_text(&syllable.base),
+                    escape_text(&syllable.reading)
+                )
+            })
+            .collect(),
+        _ => escape_text(&word.text),
+    }
+}
+
+/// Drop zero-width words (`start_ms == end_ms`) from `words`, folding each
+/// one's text into the start of the next real word rather than losing it, or
+/// dropping it outright if it's the last word in the line.
+fn collapse_zero_width_syllables(words: &[Word]) -> Vec<Word> {
+    let mut out = Vec::with_capacity(words.len());
+    let mut pending_prefix = String::new();
+    for word in words {
+        if word.start_ms == word.end_ms {
+            pending_prefix.push_str(&word.text);
+            continue;
+        }
+        if pending_prefix.is_empty() {
+            out.push(word.clone());
+        } else {
+            let mut merged = word.clone();
+            merged.text = format!("{pending_prefix}{}", word.text);
+            out.push(merged);
+            pending_prefix.clear();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Agent, AnnotatedText, LyricLine, Word};
+
+    fn sample_data() -> ParsedSourceData {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 500,
+            end_ms: 1000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.agent = Some("v1".into());
+        ParsedSourceData {
+            lines: vec![line],
+            agents: vec![Agent {
+                id: "v1".into(),
+                name: None,
+                agent_type: AgentType::Person,
+            }],
+            metadata: vec![],
+            warnings: vec![],
+            raw_metadata: vec![],
+            is_line_timing_mode: None,
+        }
+    }
+
+    #[test]
+    fn default_options_emit_capitalized_timing() {
+        let ttml = generate_ttml(&sample_data()).unwrap();
+        assert!(ttml.contains("itunes:timing=\"Word\""));
+    }
+
+    #[test]
+    fn lowercase_option_emits_lowercase_timing() {
+        let options = TtmlGenerationOptions {
+            itunes_timing_lowercase: true,
+            format: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&sample_data(), &options).unwrap();
+        assert!(ttml.contains("itunes:timing=\"word\""));
+    }
+
+    #[test]
+    fn line_timing_without_multiple_words() {
+        let mut data = sample_data();
+        data.lines[0].words.truncate(1);
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("itunes:timing=\"Line\""));
+    }
+
+    #[test]
+    fn format_ttml_time_pads_components() {
+        assert_eq!(
+            format_ttml_time(1234, TimePrecision::Milliseconds),
+            "00:00:01.234"
+        );
+        assert_eq!(
+            format_ttml_time(3_661_001, TimePrecision::Milliseconds),
+            "01:01:01.001"
+        );
+    }
+
+    #[test]
+    fn format_ttml_time_centiseconds_rounds_to_the_nearest_hundredth() {
+        assert_eq!(
+            format_ttml_time(1234, TimePrecision::Centiseconds),
+            "00:00:01.23"
+        );
+        // 123ms (12.3 centiseconds) rounds down to the nearest centisecond.
+        assert_eq!(
+            format_ttml_time(123, TimePrecision::Centiseconds),
+            "00:00:00.12"
+        );
+        // 125ms sits exactly on the boundary and rounds up.
+        assert_eq!(
+            format_ttml_time(125, TimePrecision::Centiseconds),
+            "00:00:00.13"
+        );
+        assert_eq!(
+            format_ttml_time(3_661_001, TimePrecision::Centiseconds),
+            "01:01:01.00"
+        );
+    }
+
+    #[test]
+    fn tabs_indentation_uses_tab_characters() {
+        let options = TtmlGenerationOptions {
+            format: true,
+            indent: IndentStyle::Tabs,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&sample_data(), &options).unwrap();
+        assert!(ttml.lines().any(|l| l.starts_with('\t')));
+    }
+
+    #[test]
+    fn four_space_indentation_indents_by_four() {
+        let options = TtmlGenerationOptions {
+            format: true,
+            indent: IndentStyle::Spaces(4),
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&sample_data(), &options).unwrap();
+        assert!(ttml.lines().any(|l| l.starts_with("    <head>")));
+    }
+
+    #[test]
+    fn group_agent_type_is_emitted_as_type_group() {
+        let mut data = sample_data();
+        data.agents[0].agent_type = AgentType::Group;
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("<ttm:agent type=\"group\" xml:id=\"v1\"/>"));
+    }
+
+    #[test]
+    fn empty_agent_name_is_emitted_as_self_closing() {
+        let mut data = sample_data();
+        data.agents[0].name = Some("   ".into());
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("<ttm:agent type=\"person\" xml:id=\"v1\"/>"));
+        assert!(!ttml.contains("<ttm:name>"));
+    }
+
+    #[test]
+    fn apply_offset_bakes_offset_metadata_into_emitted_times() {
+        let mut data = sample_data();
+        data.metadata.push(("offset".into(), "500".into()));
+        let options = TtmlGenerationOptions {
+            format: true,
+            apply_offset: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains(&format!(
+            "<p begin=\"{}\"",
+            format_ttml_time(500, TimePrecision::Milliseconds)
+        )));
+        assert!(!ttml.contains("key=\"offset\""));
+    }
+
+    #[test]
+    fn raw_metadata_is_written_back_verbatim() {
+        let mut data = sample_data();
+        data.raw_metadata.push(("myns:bpm".into(), "120".into()));
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("<myns:bpm>120</myns:bpm>"));
+    }
+
+    #[test]
+    fn preserve_itunes_keys_round_trips_the_original_keys() {
+        let mut first = LyricLine::new(0, 1000);
+        first.key = Some("L5".into());
+        first.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut second = LyricLine::new(1000, 2000);
+        second.key = Some("L10".into());
+        second.words.push(Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+
+        let data = ParsedSourceData {
+            lines: vec![first, second],
+            ..sample_data()
+        };
+        let options = TtmlGenerationOptions {
+            preserve_itunes_keys: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        let reparsed = crate::parse_ttml(&ttml).unwrap();
+
+        assert_eq!(reparsed.lines[0].key.as_deref(), Some("L5"));
+        assert_eq!(reparsed.lines[1].key.as_deref(), Some("L10"));
+    }
+
+    #[test]
+    fn a_fully_keyed_file_keeps_every_key_and_translation_link_after_a_round_trip() {
+        let mut first = LyricLine::new(0, 1000);
+        first.key = Some("L5".into());
+        first.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        first.translations.push(AnnotatedText {
+            lang: Some("ja".into()),
+            text: "こんにちは".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let mut second = LyricLine::new(1000, 2000);
+        second.key = Some("L10".into());
+        second.words.push(Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+        second.translations.push(AnnotatedText {
+            lang: Some("ja".into()),
+            text: "せかい".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+
+        let data = ParsedSourceData {
+            lines: vec![first, second],
+            ..sample_data()
+        };
+        let options = TtmlGenerationOptions {
+            preserve_itunes_keys: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        // Every reference to a line's own key -- its own `<p itunes:key=...>`
+        // and the head-level `<amll:translation for="...">` pointing back at
+        // it -- must be the input's original key, never a synthesized `L<n>`.
+        assert!(ttml.contains("itunes:key=\"L5\""));
+        assert!(ttml.contains("itunes:key=\"L10\""));
+        assert!(ttml.contains("for=\"L5\""));
+        assert!(ttml.contains("for=\"L10\""));
+
+        let reparsed = crate::parse_ttml(&ttml).unwrap();
+        assert_eq!(reparsed.lines[0].key.as_deref(), Some("L5"));
+        assert_eq!(reparsed.lines[1].key.as_deref(), Some("L10"));
+        assert_eq!(reparsed.lines[0].translations[0].text, "こんにちは");
+        assert_eq!(reparsed.lines[1].translations[0].text, "せかい");
+    }
+
+    #[test]
+    fn itunes_keys_are_omitted_by_default() {
+        let mut data = sample_data();
+        data.lines[0].key = Some("L5".into());
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(!ttml.contains("itunes:key"));
+    }
+
+    #[test]
+    fn a_lines_region_survives_a_generate_and_parse_round_trip() {
+        let mut data = sample_data();
+        data.lines[0].region = Some("bottom".into());
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("region=\"bottom\""));
+        let reparsed = crate::parse_ttml(&ttml).unwrap();
+        assert_eq!(reparsed.lines[0].region.as_deref(), Some("bottom"));
+    }
+
+    #[test]
+    fn default_layout_writes_translations_only_in_head() {
+        let mut data = sample_data();
+        data.lines[0].translations.push(AnnotatedText {
+            lang: Some("zh".into()),
+            text: "你好".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains("<amll:translation"));
+        assert!(!ttml.contains("x-translation"));
+    }
+
+    #[test]
+    fn inline_layout_writes_translations_only_as_spans() {
+        let mut data = sample_data();
+        data.lines[0].translations.push(AnnotatedText {
+            lang: Some("zh".into()),
+            text: "你好".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let options = TtmlGenerationOptions {
+            translation_layout: TranslationLayout::Inline,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains("ttm:role=\"x-translation\""));
+        assert!(!ttml.contains("<amll:translation"));
+    }
+
+    #[test]
+    fn both_layout_round_trips_a_single_translation_per_line() {
+        let mut data = sample_data();
+        data.lines[0].translations.push(AnnotatedText {
+            lang: Some("zh".into()),
+            text: "你好".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let options = TtmlGenerationOptions {
+            format: true,
+            translation_layout: TranslationLayout::Both,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains("<amll:translation"));
+        assert!(ttml.contains("ttm:role=\"x-translation\""));
+
+        let reparsed = crate::parse_ttml(&ttml).unwrap();
+        assert_eq!(reparsed.lines[0].translations.len(), 1);
+        assert_eq!(reparsed.lines[0].translations[0].text, "你好");
+    }
+
+    #[test]
+    fn a_line_timed_line_alongside_a_word_timed_one_round_trips_without_a_span_wrapper() {
+        let mut data = sample_data();
+        let mut verse_line = LyricLine::new(1000, 3000);
+        verse_line.words.push(Word {
+            start_ms: 1000,
+            end_ms: 3000,
+            text: "a whole line-timed verse".into(),
+            lang: None,
+            furigana: None,
+        });
+        data.lines.push(verse_line);
+
+        let ttml = generate_ttml(&data).unwrap();
+        // The document as a whole is still declared word-timed, since one of
+        // its lines genuinely is.
+        assert!(ttml.contains("itunes:timing=\"Word\""));
+        assert!(ttml.contains("a whole line-timed verse"));
+        assert!(!ttml.contains("<span begin=\"00:00:01.000\""));
+
+        let reparsed = crate::parse_ttml(&ttml).unwrap();
+        assert_eq!(reparsed.lines.len(), 2);
+        assert_eq!(reparsed.lines[0].words.len(), 2);
+        assert_eq!(reparsed.lines[1].words.len(), 1);
+        assert_eq!(reparsed.lines[1].words[0].text, "a whole line-timed verse");
+    }
+
+    #[test]
+    fn a_word_with_furigana_is_written_as_ruby_instead_of_plain_text() {
+        let mut data = sample_data();
+        data.lines[0].words[0].furigana = Some(vec![crate::types::FuriganaSyllable {
+            base: "漢字".into(),
+            reading: "かんじ".into(),
+        }]);
+
+        let ttml = generate_ttml(&data).unwrap();
+
+        assert!(ttml.contains("<ruby>漢字<rt>かんじ</rt></ruby>"));
+        assert!(!ttml.contains(">hello</span>"));
+    }
+
+    #[test]
+    fn multi_valued_metadata_keys_emit_in_the_same_order_regardless_of_input_order() {
+        let mut forward = sample_data();
+        forward.metadata.push(("artists".into(), "Alice".into()));
+        forward.metadata.push(("artists".into(), "Bob".into()));
+
+        let mut reversed = sample_data();
+        reversed.metadata.push(("artists".into(), "Bob".into()));
+        reversed.metadata.push(("artists".into(), "Alice".into()));
+
+        assert_eq!(
+            generate_ttml(&forward).unwrap(),
+            generate_ttml(&reversed).unwrap()
+        );
+    }
+
+    fn two_song_parts_data() -> ParsedSourceData {
+        let mut verse = LyricLine::new(0, 1000);
+        verse.song_part.div = Some("verse".into());
+        let mut chorus = LyricLine::new(1000, 2000);
+        chorus.song_part.div = Some("chorus".into());
+        let mut verse_again = LyricLine::new(2000, 3000);
+        verse_again.song_part.div = Some("verse".into());
+        ParsedSourceData {
+            lines: vec![verse, chorus, verse_again],
+            ..ParsedSourceData::default()
+        }
+    }
+
+    #[test]
+    fn per_song_part_strategy_starts_a_new_div_on_each_change() {
+        let ttml = generate_ttml(&two_song_parts_data()).unwrap();
+        assert_eq!(ttml.matches("<div").count(), 3);
+        assert!(ttml.contains("itunes:song-part=\"verse\""));
+        assert!(ttml.contains("itunes:song-part=\"chorus\""));
+    }
+
+    #[test]
+    fn a_p_level_song_part_override_round_trips_without_starting_a_new_div() {
+        let ttml = r#"<tt><body><div itunes:song-part="verse">
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:song-part="chorus">
+                <span begin="00:00:01.000" end="00:00:02.000">there</span>
+            </p>
+        </div></body></tt>"#;
+        let data = crate::parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].song_part.p, None);
+        assert_eq!(data.lines[1].song_part.p.as_deref(), Some("chorus"));
+
+        let regenerated = generate_ttml(&data).unwrap();
+        assert_eq!(regenerated.matches("<div").count(), 1);
+        assert!(regenerated.contains("itunes:song-part=\"verse\""));
+        assert!(regenerated.contains("itunes:song-part=\"chorus\""));
+
+        let reparsed = crate::parse_ttml(&regenerated).unwrap();
+        assert_eq!(reparsed.lines[0].song_part.div.as_deref(), Some("verse"));
+        assert_eq!(reparsed.lines[0].song_part.p, None);
+        assert_eq!(reparsed.lines[1].song_part.div.as_deref(), Some("verse"));
+        assert_eq!(reparsed.lines[1].song_part.p.as_deref(), Some("chorus"));
+    }
+
+    #[test]
+    fn single_div_strategy_puts_every_line_in_one_div() {
+        let options = TtmlGenerationOptions {
+            format: true,
+            div_strategy: DivStrategy::SingleDiv,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&two_song_parts_data(), &options).unwrap();
+        assert_eq!(ttml.matches("<div").count(), 1);
+        assert!(!ttml.contains("itunes:song-part"));
+    }
+
+    #[test]
+    fn per_line_strategy_gives_every_line_its_own_div() {
+        let options = TtmlGenerationOptions {
+            format: true,
+            div_strategy: DivStrategy::PerLine,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&two_song_parts_data(), &options).unwrap();
+        assert_eq!(ttml.matches("<div").count(), 3);
+        assert!(!ttml.contains("itunes:song-part"));
+    }
+
+    #[test]
+    fn unformatted_output_has_no_newlines() {
+        let options = TtmlGenerationOptions {
+            format: false,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&sample_data(), &options).unwrap();
+        assert_eq!(ttml.lines().count(), 1);
+    }
+
+    #[test]
+    fn estimated_output_capacity_scales_with_syllable_count() {
+        assert_eq!(
+            estimated_output_capacity(&ParsedSourceData::default()),
+            BASE_OUTPUT_CAPACITY
+        );
+        assert_eq!(
+            estimated_output_capacity(&sample_data()),
+            BASE_OUTPUT_CAPACITY + sample_data().syllable_count() * BYTES_PER_SYLLABLE_ESTIMATE
+        );
+    }
+
+    #[test]
+    fn output_buffer_is_preallocated_for_its_estimated_capacity() {
+        let ttml = generate_ttml(&sample_data()).unwrap();
+        assert!(ttml.capacity() >= estimated_output_capacity(&sample_data()));
+    }
+
+    #[test]
+    fn a_mid_line_zero_width_syllable_is_merged_into_the_next_one() {
+        let mut line = LyricLine::new(0, 2000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "hel".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 500,
+            end_ms: 500,
+            text: "lo-".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 500,
+            end_ms: 2000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        let options = TtmlGenerationOptions {
+            format: true,
+            collapse_zero_width_syllables: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains(">lo-world<"));
+        assert!(!ttml.contains(">lo-<"));
+        assert_eq!(ttml.matches("<span begin=").count(), 2);
+    }
+
+    #[test]
+    fn a_trailing_zero_width_syllable_is_dropped() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 1000,
+            end_ms: 1000,
+            text: "!".into(),
+            lang: None,
+            furigana: None,
+        });
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        let options = TtmlGenerationOptions {
+            collapse_zero_width_syllables: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(!ttml.contains('!'));
+        assert!(ttml.contains("hello"));
+    }
+
+    #[test]
+    fn zero_width_syllables_are_kept_when_the_option_is_off() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "hi".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 500,
+            end_ms: 500,
+            text: "!".into(),
+            lang: None,
+            furigana: None,
+        });
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        let ttml = generate_ttml(&data).unwrap();
+        assert!(ttml.contains('!'));
+    }
+
+    #[cfg(feature = "unicode-normalize")]
+    #[test]
+    fn nfc_normalization_composes_combining_characters() {
+        let mut data = sample_data();
+        // "e" + combining acute accent (U+0301), decomposed.
+        data.lines[0].words[0].text = "e\u{0301}cole".into();
+        let options = TtmlGenerationOptions {
+            normalize_unicode: Some(NormalizationForm::Nfc),
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains("école"));
+        assert!(!ttml.contains("e\u{0301}cole"));
+    }
+
+    #[cfg(feature = "unicode-normalize")]
+    #[test]
+    fn nfkc_normalization_folds_fullwidth_variants() {
+        let mut data = sample_data();
+        data.lines[0].words[0].text = "\u{FF21}\u{FF22}\u{FF23}".into(); // fullwidth "ABC"
+        let options = TtmlGenerationOptions {
+            normalize_unicode: Some(NormalizationForm::Nfkc),
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains(">ABC<"));
+    }
+
+    #[cfg(not(feature = "unicode-normalize"))]
+    #[test]
+    fn normalize_unicode_without_the_feature_fails_generation() {
+        let options = TtmlGenerationOptions {
+            normalize_unicode: Some(NormalizationForm::Nfc),
+            ..TtmlGenerationOptions::default()
+        };
+        assert!(generate_ttml_inner(&sample_data(), &options).is_err());
+    }
+
+    #[test]
+    fn sanitize_control_chars_strips_a_unicode_line_separator() {
+        let mut data = sample_data();
+        data.lines[0].words[0].text = format!("hi{}there", '\u{2028}');
+        let options = TtmlGenerationOptions {
+            sanitize_control_chars: true,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains(">hithere<"));
+        assert!(!ttml.contains('\u{2028}'));
+    }
+
+    #[test]
+    fn stripped_annotations_leave_no_translation_or_romanization_spans() {
+        let mut data = sample_data();
+        data.lines[0]
+            .translations
+            .push(crate::types::AnnotatedText {
+                text: "hola".into(),
+                ..Default::default()
+            });
+        data.lines[0]
+            .romanizations
+            .push(crate::types::AnnotatedText {
+                text: "hello".into(),
+                ..Default::default()
+            });
+        crate::ops::strip_annotations(&mut data.lines);
+        let options = TtmlGenerationOptions {
+            translation_layout: TranslationLayout::Both,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(!ttml.contains("x-translation"));
+        assert!(!ttml.contains("x-roman"));
+    }
+
+    #[test]
+    fn sanitize_control_chars_off_by_default_keeps_the_line_separator() {
+        let mut data = sample_data();
+        data.lines[0].words[0].text = format!("hi{}there", '\u{2028}');
+        let ttml = generate_ttml_inner(&data, &TtmlGenerationOptions::default()).unwrap();
+        assert!(ttml.contains('\u{2028}'));
+    }
+
+    #[test]
+    fn milliseconds_precision_is_the_default() {
+        let mut data = sample_data();
+        data.lines[0].words[0].end_ms = 1234;
+        let ttml = generate_ttml_inner(&data, &TtmlGenerationOptions::default()).unwrap();
+        assert!(ttml.contains(&format_ttml_time(1234, TimePrecision::Milliseconds)));
+    }
+
+    #[test]
+    fn centiseconds_precision_rounds_every_timestamp_written() {
+        let mut data = sample_data();
+        data.lines[0].words[0].end_ms = 1234;
+        let options = TtmlGenerationOptions {
+            time_precision: TimePrecision::Centiseconds,
+            ..TtmlGenerationOptions::default()
+        };
+        let ttml = generate_ttml_inner(&data, &options).unwrap();
+        assert!(ttml.contains(&format_ttml_time(1234, TimePrecision::Centiseconds)));
+        assert!(!ttml.contains(&format_ttml_time(1234, TimePrecision::Milliseconds)));
+    }
+}
@@ -0,0 +1,104 @@
+//! Generation of Kugou's KRC (逐字歌词) format.
+
+use std::fmt::Write as _;
+
+use crate::types::ParsedSourceData;
+
+/// Metadata keys with a dedicated KRC header line, in the order KRC readers
+/// expect them.
+const KRC_HEADER_KEYS: &[(&str, &str)] = &[("musicName", "ti"), ("artists", "ar"), ("album", "al")];
+
+/// Render `data` as the plaintext body of a KRC file (i.e. before the
+/// format's usual `zlib`+XOR encryption is applied on top).
+///
+/// The header carries whichever of `musicName`/`artists`/`album` are present in
+/// [`ParsedSourceData::metadata`] as `[ti:]`/`[ar:]`/`[al:]` lines, followed
+/// by the mandatory `[offset:0]`. Each line is then
+/// `[start,duration]<word_offset,word_duration,0>word...`, with word offsets
+/// relative to the start of the *line*.
+pub fn generate_krc(data: &ParsedSourceData) -> String {
+    let mut out = String::new();
+    for (meta_key, krc_key) in KRC_HEADER_KEYS {
+        if let Some((_, value)) = data.metadata.iter().find(|(k, _)| k == meta_key) {
+            writeln!(out, "[{krc_key}:{value}]").unwrap();
+        }
+    }
+    out.push_str("[offset:0]\n");
+
+    for line in &data.lines {
+        let duration = line.end_ms.saturating_sub(line.start_ms);
+        write!(out, "[{},{}]", line.start_ms, duration).unwrap();
+        for word in &line.words {
+            let word_offset = word.start_ms.saturating_sub(line.start_ms);
+            let word_duration = word.end_ms.saturating_sub(word.start_ms);
+            write!(out, "<{word_offset},{word_duration},0>{}", word.text).unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{LyricLine, Word};
+
+    fn sample_data() -> ParsedSourceData {
+        let mut line = LyricLine::new(1000, 3000);
+        line.words.push(Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "hello".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 2000,
+            end_ms: 3000,
+            text: "world".into(),
+            lang: None,
+            furigana: None,
+        });
+        ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn word_offsets_are_relative_to_the_line_start() {
+        let krc = generate_krc(&sample_data());
+        assert_eq!(
+            krc,
+            "[offset:0]\n[1000,2000]<0,1000,0>hello<1000,1000,0>world\n"
+        );
+    }
+
+    #[test]
+    fn header_carries_known_metadata_as_krc_tags() {
+        let mut data = sample_data();
+        data.metadata
+            .push(("musicName".into(), "Groundless".into()));
+        data.metadata.push(("artists".into(), "Nurulu".into()));
+        let krc = generate_krc(&data);
+        assert!(krc.starts_with("[ti:Groundless]\n[ar:Nurulu]\n[offset:0]\n"));
+    }
+
+    #[test]
+    fn line_without_word_timing_still_emits_one_span() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "whole line".into(),
+            lang: None,
+            furigana: None,
+        });
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        let krc = generate_krc(&data);
+        assert_eq!(krc, "[offset:0]\n[0,1000]<0,1000,0>whole line\n");
+    }
+}
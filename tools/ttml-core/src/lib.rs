@@ -0,0 +1,118 @@
+//! Parsing and generation of Apple Music / AMLL compatible TTML lyric files.
+
+mod errors;
+mod furigana;
+mod generator;
+mod krc;
+mod lys;
+mod ops;
+mod parser;
+mod qrc;
+mod richsync;
+mod tokenize;
+mod types;
+
+pub use errors::{ConvertError, Locale};
+pub use furigana::align_furigana;
+pub use generator::{
+    format_ttml_time, generate_ttml, generate_ttml_inner, DivStrategy, IndentStyle,
+    NormalizationForm, TimePrecision, TranslationLayout, TtmlGenerationOptions,
+};
+pub use krc::generate_krc;
+pub use lys::{generate_lys, parse_lys};
+pub use ops::{
+    apply_timings, auto_split_word, collect_timings, distribute_evenly,
+    ensure_translation_placeholder, levenshtein_distance, merge_repeated_syllables,
+    merge_short_lines, normalize_translation_languages, remap_agents, reverse_timing,
+    strip_annotations, to_keyframes, validate_agent_references, validate_song_parts,
+    validate_track_duration, validate_translation_without_content, FrameState,
+    LanguageEquivalenceTable, TimingRef, TimingSlot,
+};
+pub use parser::{
+    parse_ttml, parse_ttml_bytes, parse_ttml_time, parse_ttml_with_options, validate_ttml_quick,
+    TtmlParsingOptions,
+};
+pub use qrc::parse_qrc;
+pub use richsync::generate_richsync;
+pub use tokenize::{auto_tokenize, get_char_type, CharType};
+pub use types::{
+    Agent, AgentType, AnnotatedText, ContentType, FuriganaSyllable, LyricLine, ParsedSourceData,
+    SongPart, ValidationIssue, Word,
+};
+
+/// Which on-disk lyric format [`parse_any`] should read `content` as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricFormat {
+    /// Apple Music / AMLL TTML XML.
+    Ttml,
+    /// AMLL's Lyricify Syllable (`.lys`) format.
+    Lys,
+    /// QQ Music's QRC format.
+    Qrc,
+}
+
+/// Parse `content` as `format`, mapping the result into a single
+/// [`ParsedSourceData`] regardless of which format it came from -- so a
+/// caller re-ingesting a `.lys`/`.qrc` file that `rebuild-folder` produced
+/// doesn't need its own per-format dispatch. `options` only affects
+/// [`LyricFormat::Ttml`] input; `.lys` and `.qrc` have no comparable parsing
+/// knobs of their own, so it's ignored for those.
+pub fn parse_any(
+    content: &str,
+    format: LyricFormat,
+    options: &TtmlParsingOptions,
+) -> Result<ParsedSourceData, ConvertError> {
+    match format {
+        LyricFormat::Ttml => parse_ttml_with_options(content, options),
+        LyricFormat::Lys => parse_lys(content),
+        LyricFormat::Qrc => parse_qrc(content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_any_dispatches_lys_content_to_the_lys_parser() {
+        let data = parse_any(
+            "[1]hi(0,1000)\n",
+            LyricFormat::Lys,
+            &TtmlParsingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+
+    #[test]
+    fn parse_any_dispatches_qrc_content_to_the_qrc_parser() {
+        let data = parse_any(
+            "[offset:0]\n[0,1000]hi(0,1000)\n",
+            LyricFormat::Qrc,
+            &TtmlParsingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+
+    #[test]
+    fn parse_any_dispatches_ttml_content_to_the_ttml_parser() {
+        let ttml = generate_ttml(&{
+            let mut line = LyricLine::new(0, 1000);
+            line.words.push(Word {
+                start_ms: 0,
+                end_ms: 1000,
+                text: "hi".into(),
+                lang: None,
+                furigana: None,
+            });
+            ParsedSourceData {
+                lines: vec![line],
+                ..Default::default()
+            }
+        })
+        .unwrap();
+        let data = parse_any(&ttml, LyricFormat::Ttml, &TtmlParsingOptions::default()).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+}
@@ -0,0 +1,320 @@
+//! Generation and parsing of AMLL's Lyricify Syllable (`.lys`) format,
+//! including its per-line attribute bit for background vocals and duet
+//! side.
+
+use std::fmt::Write as _;
+
+use crate::errors::ConvertError;
+use crate::types::{Agent, AgentType, LyricLine, ParsedSourceData, Word};
+
+/// Set on top of a line's duet-side bit for a background segment, matching
+/// the format's convention of `5`/`6` for background-left/background-right
+/// (`1|4`/`2|4`).
+const LYS_BACKGROUND_BIT: u8 = 4;
+
+/// Render `data` as a Lyricify Syllable (`.lys`) file: one
+/// `[attribute]word1(start,duration)word2(start,duration)...` line per
+/// [`LyricLine`], followed by a second attribute line for any
+/// [`LyricLine::background`] syllables sharing it.
+///
+/// The attribute's low bits mark which side of a duet the line is sung from:
+/// the first agent id encountered is side `1` (left), any other agent id is
+/// side `2` (right), and a line with no agent at all stays on side `1`.
+/// [`LYS_BACKGROUND_BIT`] is added on top for the background line.
+pub fn generate_lys(data: &ParsedSourceData) -> String {
+    let mut out = String::new();
+    let mut left_agent: Option<&str> = None;
+    for line in &data.lines {
+        let side = line_side(line, &mut left_agent);
+        write_lys_line(&mut out, side, &line.words);
+        if !line.background.is_empty() {
+            write_lys_line(&mut out, side | LYS_BACKGROUND_BIT, &line.background);
+        }
+    }
+    out
+}
+
+/// The duet side bit (`1` = left, `2` = right) for `line`, remembering the
+/// first agent id seen as the left side via `left_agent`.
+fn line_side<'a>(line: &'a LyricLine, left_agent: &mut Option<&'a str>) -> u8 {
+    let Some(agent) = line.agent.as_deref() else {
+        return 1;
+    };
+    match left_agent {
+        None => {
+            *left_agent = Some(agent);
+            1
+        }
+        Some(first) if *first == agent => 1,
+        Some(_) => 2,
+    }
+}
+
+fn write_lys_line(out: &mut String, attribute: u8, words: &[Word]) {
+    write!(out, "[{attribute}]").unwrap();
+    for word in words {
+        let duration = word.end_ms.saturating_sub(word.start_ms);
+        write!(out, "{}({},{})", word.text, word.start_ms, duration).unwrap();
+    }
+    out.push('\n');
+}
+
+/// Parse a Lyricify Syllable (`.lys`) file back into [`ParsedSourceData`].
+///
+/// The format itself has no notion of an agent's own identity -- only which
+/// side of a duet a line is on -- so this can't recover the original agent
+/// ids [`generate_lys`] started from. A side-`1` line comes back with no
+/// agent (matching the common no-duet case), and every side-`2` line is
+/// given a synthesized `"v2"` agent, added to [`ParsedSourceData::agents`]
+/// the first time it's seen.
+pub fn parse_lys(content: &str) -> Result<ParsedSourceData, ConvertError> {
+    let mut lines = Vec::new();
+    let mut agents = Vec::new();
+
+    for (line_no, raw_line) in content.lines().enumerate() {
+        let raw_line = raw_line.trim();
+        if raw_line.is_empty() {
+            continue;
+        }
+        let (attribute, words) = parse_lys_line(raw_line, line_no + 1)?;
+        let is_background = attribute & LYS_BACKGROUND_BIT != 0;
+        let side = attribute & !LYS_BACKGROUND_BIT;
+
+        if is_background {
+            let Some(previous) = lines.last_mut() else {
+                return Err(ConvertError::Malformed(format!(
+                    "lys line {} is a background line with no preceding main line",
+                    line_no + 1
+                )));
+            };
+            let previous: &mut LyricLine = previous;
+            previous.background = words;
+            continue;
+        }
+
+        let mut line = LyricLine::new(
+            words.first().map_or(0, |w| w.start_ms),
+            words.last().map_or(0, |w| w.end_ms),
+        );
+        line.words = words;
+        if side == 2 {
+            if !agents.iter().any(|a: &Agent| a.id == "v2") {
+                agents.push(Agent {
+                    id: "v2".into(),
+                    name: None,
+                    agent_type: AgentType::Person,
+                });
+            }
+            line.agent = Some("v2".into());
+        }
+        lines.push(line);
+    }
+
+    Ok(ParsedSourceData {
+        lines,
+        agents,
+        ..Default::default()
+    })
+}
+
+/// Parse one `[attribute]word1(start,duration)word2(start,duration)...`
+/// line into its attribute byte and syllables.
+fn parse_lys_line(raw: &str, line_no: usize) -> Result<(u8, Vec<Word>), ConvertError> {
+    if !raw.starts_with('[') {
+        return Err(ConvertError::Malformed(format!(
+            "lys line {line_no} doesn't start with an attribute in '[...]'"
+        )));
+    }
+    let close = raw.find(']').ok_or_else(|| {
+        ConvertError::Malformed(format!("lys line {line_no} is missing a closing ']'"))
+    })?;
+    let attribute: u8 = raw[1..close].parse().map_err(|_| {
+        ConvertError::Malformed(format!(
+            "lys line {line_no} has a non-numeric attribute `{}`",
+            &raw[1..close]
+        ))
+    })?;
+
+    let mut words = Vec::new();
+    let mut rest = &raw[close + 1..];
+    while !rest.is_empty() {
+        let open = rest.find('(').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "lys line {line_no} has a syllable with no '(start,duration)'"
+            ))
+        })?;
+        let text = &rest[..open];
+        let close_paren = rest[open..].find(')').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "lys line {line_no} has an unterminated '(start,duration)'"
+            ))
+        })? + open;
+        let timing = &rest[open + 1..close_paren];
+        let (start_str, duration_str) = timing.split_once(',').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "lys line {line_no} syllable timing `{timing}` is missing a comma"
+            ))
+        })?;
+        let start_ms: u64 = start_str.trim().parse().map_err(|_| {
+            ConvertError::Malformed(format!(
+                "lys line {line_no} has a non-numeric syllable start `{start_str}`"
+            ))
+        })?;
+        let duration_ms: u64 = duration_str.trim().parse().map_err(|_| {
+            ConvertError::Malformed(format!(
+                "lys line {line_no} has a non-numeric syllable duration `{duration_str}`"
+            ))
+        })?;
+        words.push(Word {
+            start_ms,
+            end_ms: start_ms + duration_ms,
+            text: text.to_string(),
+            lang: None,
+            furigana: None,
+        });
+        rest = &rest[close_paren + 1..];
+    }
+    Ok((attribute, words))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LyricLine;
+
+    fn word(text: &str, start_ms: u64, end_ms: u64) -> Word {
+        Word {
+            start_ms,
+            end_ms,
+            text: text.into(),
+            lang: None,
+            furigana: None,
+        }
+    }
+
+    #[test]
+    fn a_line_with_no_agent_is_left_side_main_vocal() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(word("hi", 0, 1000));
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        assert_eq!(generate_lys(&data), "[1]hi(0,1000)\n");
+    }
+
+    #[test]
+    fn the_first_agent_seen_is_the_left_side() {
+        let mut line = LyricLine::new(0, 1000);
+        line.agent = Some("v1".into());
+        line.words.push(word("hi", 0, 1000));
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        assert_eq!(generate_lys(&data), "[1]hi(0,1000)\n");
+    }
+
+    #[test]
+    fn a_second_distinct_agent_is_the_right_side() {
+        let mut lead = LyricLine::new(0, 1000);
+        lead.agent = Some("v1".into());
+        lead.words.push(word("hi", 0, 1000));
+        let mut duet = LyricLine::new(1000, 2000);
+        duet.agent = Some("v2".into());
+        duet.words.push(word("there", 1000, 2000));
+        let data = ParsedSourceData {
+            lines: vec![lead, duet],
+            ..Default::default()
+        };
+        assert_eq!(generate_lys(&data), "[1]hi(0,1000)\n[2]there(1000,1000)\n");
+    }
+
+    #[test]
+    fn a_background_line_gets_its_own_attribute_line_with_the_background_bit_set() {
+        let mut line = LyricLine::new(0, 2000);
+        line.agent = Some("v1".into());
+        line.words.push(word("hi", 0, 1000));
+        line.background.push(word("oooh", 1000, 2000));
+        let data = ParsedSourceData {
+            lines: vec![line],
+            ..Default::default()
+        };
+        assert_eq!(generate_lys(&data), "[1]hi(0,1000)\n[5]oooh(1000,1000)\n");
+    }
+
+    #[test]
+    fn a_right_side_background_line_uses_bit_six() {
+        let mut lead = LyricLine::new(0, 1000);
+        lead.agent = Some("v1".into());
+        lead.words.push(word("hi", 0, 1000));
+        let mut duet = LyricLine::new(1000, 3000);
+        duet.agent = Some("v2".into());
+        duet.words.push(word("there", 1000, 2000));
+        duet.background.push(word("ooh", 2000, 3000));
+        let data = ParsedSourceData {
+            lines: vec![lead, duet],
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_lys(&data),
+            "[1]hi(0,1000)\n[2]there(1000,1000)\n[6]ooh(2000,1000)\n"
+        );
+    }
+
+    #[test]
+    fn parse_lys_reads_back_a_single_line_with_no_agent() {
+        let data = parse_lys("[1]hi(0,1000)\n").unwrap();
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].start_ms, 0);
+        assert_eq!(data.lines[0].end_ms, 1000);
+        assert_eq!(data.lines[0].words, vec![word("hi", 0, 1000)]);
+        assert_eq!(data.lines[0].agent, None);
+    }
+
+    #[test]
+    fn parse_lys_assigns_side_two_lines_a_synthesized_v2_agent() {
+        let data = parse_lys("[1]hi(0,1000)\n[2]there(1000,1000)\n").unwrap();
+        assert_eq!(data.lines[0].agent, None);
+        assert_eq!(data.lines[1].agent.as_deref(), Some("v2"));
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.agents[0].id, "v2");
+    }
+
+    #[test]
+    fn parse_lys_attaches_a_background_line_to_the_line_before_it() {
+        let data = parse_lys("[1]hi(0,1000)\n[5]oooh(1000,1000)\n").unwrap();
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].background, vec![word("oooh", 1000, 2000)]);
+    }
+
+    #[test]
+    fn a_generate_then_parse_round_trip_keeps_words_and_background() {
+        let mut lead = LyricLine::new(0, 1000);
+        lead.agent = Some("v1".into());
+        lead.words.push(word("hi", 0, 1000));
+        let mut duet = LyricLine::new(1000, 3000);
+        duet.agent = Some("v2".into());
+        duet.words.push(word("there", 1000, 2000));
+        duet.background.push(word("ooh", 2000, 3000));
+        let data = ParsedSourceData {
+            lines: vec![lead, duet],
+            ..Default::default()
+        };
+        let lys = generate_lys(&data);
+        let reparsed = parse_lys(&lys).unwrap();
+        assert_eq!(reparsed.lines[0].words, data.lines[0].words);
+        assert_eq!(reparsed.lines[1].words, data.lines[1].words);
+        assert_eq!(reparsed.lines[1].background, data.lines[1].background);
+    }
+
+    #[test]
+    fn parse_lys_rejects_a_line_missing_its_attribute_brackets() {
+        assert!(parse_lys("hi(0,1000)\n").is_err());
+    }
+
+    #[test]
+    fn parse_lys_rejects_a_background_line_with_nothing_before_it() {
+        assert!(parse_lys("[5]oooh(0,1000)\n").is_err());
+    }
+}
@@ -0,0 +1,1333 @@
+//! Bulk operations over already-parsed lyric lines, used by the CLI tools
+//! to clean up or transform a `lyrics/*.ttml` submission.
+
+use std::collections::HashMap;
+
+use crate::tokenize::{auto_tokenize, get_char_type, CharType};
+use crate::types::{Agent, AnnotatedText, LyricLine, ValidationIssue, Word};
+
+/// One sampled animation frame for a per-syllable typewriter fade-in: which
+/// line/word is active (if any) and how far playback has progressed through
+/// it, from `0.0` (just started) to `1.0` (finished).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameState {
+    pub line_index: Option<usize>,
+    pub word_index: Option<usize>,
+    pub progress: f32,
+}
+
+impl FrameState {
+    fn gap() -> Self {
+        Self {
+            line_index: None,
+            word_index: None,
+            progress: 0.0,
+        }
+    }
+}
+
+/// Sample `lines` at `fps` frames per second into a [`FrameState`] per
+/// frame, for a frontend to drive a word-by-word fade-in animation without
+/// re-implementing the timing lookup itself. Frames that fall in a gap
+/// (before the first word, between lines, or past the last word) come back
+/// as [`FrameState::gap`].
+///
+/// Returns one frame for every `1000.0 / fps` milliseconds up to and
+/// including the last line's `end_ms`.
+#[must_use]
+pub fn to_keyframes(lines: &[LyricLine], fps: f64) -> Vec<FrameState> {
+    let total_ms = lines.iter().map(|line| line.end_ms).max().unwrap_or(0);
+    let frame_ms = 1000.0 / fps;
+    let frame_count = (total_ms as f64 / frame_ms).ceil() as u64 + 1;
+
+    (0..frame_count)
+        .map(|frame| {
+            let t = (frame as f64 * frame_ms) as u64;
+            frame_state_at(lines, t)
+        })
+        .collect()
+}
+
+/// Which per-syllable timing a [`TimingRef`] points at within its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingSlot {
+    Word(usize),
+    Background(usize),
+}
+
+/// One syllable's timing, addressed by its position in `lines` rather than
+/// by value, so a visual editor can hand back an edited flat list and have
+/// it written back to the exact syllable it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingRef {
+    pub line_index: usize,
+    pub slot: TimingSlot,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Flatten every syllable's timing across `lines` into a stable-ordered
+/// list: line by line, main-track words before that line's background
+/// syllables, each in their existing order. Pair with [`apply_timings`] to
+/// write edits back.
+#[must_use]
+pub fn collect_timings(lines: &[LyricLine]) -> Vec<TimingRef> {
+    let mut timings = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        for (word_index, word) in line.words.iter().enumerate() {
+            timings.push(TimingRef {
+                line_index,
+                slot: TimingSlot::Word(word_index),
+                start_ms: word.start_ms,
+                end_ms: word.end_ms,
+            });
+        }
+        for (word_index, word) in line.background.iter().enumerate() {
+            timings.push(TimingRef {
+                line_index,
+                slot: TimingSlot::Background(word_index),
+                start_ms: word.start_ms,
+                end_ms: word.end_ms,
+            });
+        }
+    }
+    timings
+}
+
+/// Write `timings` back onto `lines`, matching each [`TimingRef`] to the
+/// syllable it was collected from by `line_index`/`slot`. A reference whose
+/// index no longer exists (the line list was edited in the meantime) is
+/// skipped rather than treated as an error, since the editor round-trip
+/// this exists for only ever hands back what [`collect_timings`] produced.
+pub fn apply_timings(lines: &mut [LyricLine], timings: &[TimingRef]) {
+    for timing in timings {
+        let Some(line) = lines.get_mut(timing.line_index) else {
+            continue;
+        };
+        let word = match timing.slot {
+            TimingSlot::Word(word_index) => line.words.get_mut(word_index),
+            TimingSlot::Background(word_index) => line.background.get_mut(word_index),
+        };
+        let Some(word) = word else {
+            continue;
+        };
+        word.start_ms = timing.start_ms;
+        word.end_ms = timing.end_ms;
+    }
+}
+
+fn frame_state_at(lines: &[LyricLine], t: u64) -> FrameState {
+    for (line_index, line) in lines.iter().enumerate() {
+        if t < line.start_ms || t >= line.end_ms {
+            continue;
+        }
+        for (word_index, word) in line.words.iter().enumerate() {
+            if t >= word.start_ms && t < word.end_ms {
+                let duration = word.end_ms.saturating_sub(word.start_ms);
+                let progress = if duration == 0 {
+                    1.0
+                } else {
+                    (t - word.start_ms) as f32 / duration as f32
+                };
+                return FrameState {
+                    line_index: Some(line_index),
+                    word_index: Some(word_index),
+                    progress,
+                };
+            }
+        }
+        return FrameState::gap();
+    }
+    FrameState::gap()
+}
+
+/// Merge adjacent lines that are shorter than `min_line_ms` and share the
+/// same agent into a single line, concatenating their syllables and
+/// translations/romanizations in order.
+///
+/// A merge never crosses a `song_part` boundary (comparing
+/// [`SongPart::effective`](crate::SongPart::effective)), so verse/chorus
+/// segmentation is preserved even when its lines are individually short.
+pub fn merge_short_lines(lines: &mut Vec<LyricLine>, min_line_ms: u64) {
+    let mut merged: Vec<LyricLine> = Vec::with_capacity(lines.len());
+    for line in lines.drain(..) {
+        if let Some(last) = merged.last_mut() {
+            let last_duration = last.end_ms.saturating_sub(last.start_ms);
+            let mergeable = last_duration < min_line_ms
+                && last.agent == line.agent
+                && last.song_part.effective() == line.song_part.effective();
+            if mergeable {
+                last.end_ms = line.end_ms;
+                last.words.extend(line.words);
+                last.background.extend(line.background);
+                last.translations.extend(line.translations);
+                last.romanizations.extend(line.romanizations);
+                continue;
+            }
+        }
+        merged.push(line);
+    }
+    *lines = merged;
+}
+
+/// Call [`LyricLine::clear_annotations`] on every line, for producing a
+/// "clean" main-only export in one pass instead of mapping over `lines`
+/// by hand at each call site.
+pub fn strip_annotations(lines: &mut [LyricLine]) {
+    for line in lines {
+        line.clear_annotations();
+    }
+}
+
+/// A table of interchangeable `xml:lang` tags, used by
+/// [`normalize_translation_languages`] to treat e.g. `zh-CN` and `zh-Hans`
+/// as the same language. Each group's first tag is the canonical one that
+/// survives normalization.
+///
+/// Empty by default: deciding two tags mean the same language is a
+/// judgement call the source TTML didn't make, so callers must opt in by
+/// building a table (or starting from [`LanguageEquivalenceTable::default_groups`]).
+#[derive(Debug, Clone, Default)]
+pub struct LanguageEquivalenceTable {
+    groups: Vec<Vec<String>>,
+}
+
+impl LanguageEquivalenceTable {
+    /// A reasonable starting point covering the Chinese script variants
+    /// that show up most often as duplicate translations.
+    pub fn default_groups() -> Self {
+        Self {
+            groups: vec![
+                vec!["zh-CN".into(), "zh-Hans".into(), "zh-Hans-CN".into()],
+                vec!["zh-TW".into(), "zh-Hant".into(), "zh-Hant-TW".into()],
+            ],
+        }
+    }
+
+    /// Add a group of tags that should all be treated as equivalent, with
+    /// the first entry as the canonical tag.
+    pub fn with_group(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.groups.push(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn canonical<'a>(&'a self, lang: &'a str) -> &'a str {
+        self.groups
+            .iter()
+            .find(|group| group.iter().any(|tag| tag.eq_ignore_ascii_case(lang)))
+            .map(|group| group[0].as_str())
+            .unwrap_or(lang)
+    }
+}
+
+/// Merge each line's translations (and separately, romanizations) whose
+/// `xml:lang` tags are equivalent under `table`, keeping the first
+/// occurrence of each equivalence group and dropping later duplicates.
+/// Lines are otherwise left untouched, so this is safe to run after
+/// [`merge_short_lines`].
+pub fn normalize_translation_languages(lines: &mut [LyricLine], table: &LanguageEquivalenceTable) {
+    for line in lines {
+        dedup_by_canonical_lang(&mut line.translations, table);
+        dedup_by_canonical_lang(&mut line.romanizations, table);
+    }
+}
+
+fn dedup_by_canonical_lang(texts: &mut Vec<AnnotatedText>, table: &LanguageEquivalenceTable) {
+    let mut seen_langs: Vec<String> = Vec::new();
+    texts.retain(|text| {
+        let Some(lang) = &text.lang else {
+            return true;
+        };
+        let canonical = table.canonical(lang).to_string();
+        if seen_langs.contains(&canonical) {
+            false
+        } else {
+            seen_langs.push(canonical);
+            true
+        }
+    });
+}
+
+/// Give every line in `lines` a translation tagged `lang`, inserting an
+/// empty one (aligned to the line, i.e. no timing of its own beyond that)
+/// for any line that doesn't already have one. Meant for a bilingual
+/// display that reserves a translation slot on every line so the layout
+/// doesn't jump around when a translator skipped a handful of lines.
+///
+/// A line that already has a `lang` translation is left untouched, even if
+/// its text happens to already be empty.
+pub fn ensure_translation_placeholder(lines: &mut [LyricLine], lang: &str) {
+    for line in lines {
+        let has_translation = line
+            .translations
+            .iter()
+            .any(|t| t.lang.as_deref() == Some(lang));
+        if !has_translation {
+            line.translations.push(AnnotatedText {
+                lang: Some(lang.to_string()),
+                text: String::new(),
+                scheme: None,
+                syllables: Vec::new(),
+            });
+        }
+    }
+}
+
+/// Redistribute each line's main-track syllable times evenly across
+/// `line.start_ms..line.end_ms`, ignoring whatever timing the syllables
+/// carried before. Useful for turning a line-timed draft into a rough
+/// pseudo-word-timed one that a contributor can then hand-adjust, rather
+/// than leaving every syllable pinned to the line's full duration.
+///
+/// Lines with no words are left untouched.
+pub fn distribute_evenly(lines: &mut [LyricLine]) {
+    for line in lines {
+        let word_count = line.words.len() as u64;
+        if word_count == 0 {
+            continue;
+        }
+        let duration = line.end_ms.saturating_sub(line.start_ms);
+        for (i, word) in line.words.iter_mut().enumerate() {
+            let i = i as u64;
+            word.start_ms = line.start_ms + duration * i / word_count;
+            word.end_ms = line.start_ms + duration * (i + 1) / word_count;
+        }
+    }
+}
+
+/// Relative weight of a single [`auto_tokenize`] token when distributing a
+/// syllable's timing across its sub-tokens in [`auto_split_word`]: letters
+/// and digits count one per character, while a token that's entirely
+/// punctuation/symbols (and so read near-instantly, e.g. a trailing "!")
+/// counts for a fraction of that so it doesn't steal a disproportionate
+/// share of the syllable's duration. A bare space carries no weight at all,
+/// since it marks a word boundary rather than something sung.
+fn token_weight(token: &str) -> f64 {
+    if token == " " {
+        0.0
+    } else if token
+        .chars()
+        .all(|c| matches!(get_char_type(c), CharType::Other))
+    {
+        0.5
+    } else {
+        token.chars().count() as f64
+    }
+}
+
+/// Split a single [`Word`] into several sub-syllables via
+/// [`auto_tokenize`], distributing `word`'s timing window across them by
+/// [`token_weight`] instead of splitting it evenly.
+///
+/// A syllable that already spans more than one word (e.g. "hello world",
+/// captured whole by an importer that doesn't understand word boundaries)
+/// is split at its spaces first, and each space is kept as its own
+/// zero-duration token between the words, so [`auto_tokenize`]'s
+/// dictionary-based hyphenation only ever splits within a single word
+/// rather than across the whole phrase.
+///
+/// A syllable that comes back as a single token (nothing to split) is
+/// returned unchanged, as a one-element `Vec`.
+#[must_use]
+pub fn auto_split_word(word: &Word) -> Vec<Word> {
+    let mut tokens: Vec<(String, f64)> = Vec::new();
+    for (i, part) in word.text.split(' ').enumerate() {
+        if i > 0 {
+            tokens.push((" ".to_string(), token_weight(" ")));
+        }
+        for token in auto_tokenize(part) {
+            let weight = token_weight(&token);
+            tokens.push((token, weight));
+        }
+    }
+    if tokens.len() <= 1 {
+        return vec![word.clone()];
+    }
+
+    let total_weight: f64 = tokens.iter().map(|(_, weight)| weight).sum();
+    let duration = word.end_ms.saturating_sub(word.start_ms) as f64;
+    let last = tokens.len() - 1;
+    let mut cursor = word.start_ms;
+    let mut accumulated_weight = 0.0;
+    let mut out = Vec::with_capacity(tokens.len());
+    for (i, (text, weight)) in tokens.into_iter().enumerate() {
+        accumulated_weight += weight;
+        let end_ms = if i == last || total_weight == 0.0 {
+            word.end_ms
+        } else {
+            word.start_ms + (duration * accumulated_weight / total_weight) as u64
+        };
+        out.push(Word {
+            start_ms: cursor,
+            end_ms,
+            text,
+            lang: word.lang.clone(),
+            furigana: None,
+        });
+        cursor = end_ms;
+    }
+    out
+}
+
+/// Rename agent ids across `lines` and `agents` in one pass, per `mapping`
+/// (old id -> new id). Useful for turning auto-generated ids (`v1`, `v2`)
+/// into semantic ones once a contributor has identified the performers.
+///
+/// If a rename's target id already exists in `agents`, the renamed entry is
+/// merged into it instead of creating a duplicate: the existing entry's
+/// `name` and `agent_type` win, since it's the id already considered
+/// canonical, and only its own `name` (if unset) is backfilled from the one
+/// being renamed away.
+pub fn remap_agents(
+    lines: &mut [LyricLine],
+    agents: &mut Vec<Agent>,
+    mapping: &HashMap<String, String>,
+) {
+    for line in lines.iter_mut() {
+        if let Some(agent_id) = line.agent.as_mut() {
+            if let Some(new_id) = mapping.get(agent_id) {
+                *agent_id = new_id.clone();
+            }
+        }
+    }
+
+    for (old_id, new_id) in mapping {
+        if old_id == new_id {
+            continue;
+        }
+        let Some(pos) = agents.iter().position(|a| &a.id == old_id) else {
+            continue;
+        };
+        let renamed = agents.remove(pos);
+        if let Some(existing) = agents.iter_mut().find(|a| &a.id == new_id) {
+            if existing.name.is_none() {
+                existing.name = renamed.name;
+            }
+        } else {
+            agents.push(Agent {
+                id: new_id.clone(),
+                name: renamed.name,
+                agent_type: renamed.agent_type,
+            });
+        }
+    }
+}
+
+/// Collapse runs of syllables that share the same text and sit back-to-back
+/// in time (one starting exactly when the previous ends) into a single
+/// syllable spanning their union. Some sources split a held note into
+/// several identical syllables (e.g. "あ" "あ" "あ" for one long "ah"); left
+/// alone, a frontend would flash the same character three times instead of
+/// holding it once.
+///
+/// A run only merges while its text and `xml:lang` both stay identical and
+/// there's no gap between syllables; anything else is left as its own word.
+pub fn merge_repeated_syllables(lines: &mut [LyricLine]) {
+    for line in lines {
+        line.words = merge_adjacent_repeats(std::mem::take(&mut line.words));
+    }
+}
+
+fn merge_adjacent_repeats(words: Vec<Word>) -> Vec<Word> {
+    let mut merged: Vec<Word> = Vec::with_capacity(words.len());
+    for word in words {
+        if let Some(last) = merged.last_mut() {
+            let mergeable =
+                last.text == word.text && last.lang == word.lang && last.end_ms == word.start_ms;
+            if mergeable {
+                last.end_ms = word.end_ms;
+                continue;
+            }
+        }
+        merged.push(word);
+    }
+    merged
+}
+
+/// Mirror `lines` in time around `total_ms`: every timestamp `t` becomes
+/// `total_ms - t`, and lines/words/background syllables are all reordered
+/// so the mirrored timing stays monotonically increasing (what used to play
+/// last now plays first). A debugging aid for exercising the generator
+/// against timing that looks nothing like a real recording -- not something
+/// an actual submission would ever want.
+pub fn reverse_timing(lines: &mut [LyricLine], total_ms: u64) {
+    lines.reverse();
+    for line in lines.iter_mut() {
+        let start = total_ms.saturating_sub(line.end_ms);
+        let end = total_ms.saturating_sub(line.start_ms);
+        line.start_ms = start;
+        line.end_ms = end;
+        reverse_word_timing(&mut line.words, total_ms);
+        reverse_word_timing(&mut line.background, total_ms);
+    }
+}
+
+/// `itunes:song-part` values this crate recognizes as standard; anything
+/// else is flagged by [`validate_song_parts`] as a likely typo.
+const STANDARD_SONG_PARTS: &[&str] = &[
+    "verse",
+    "chorus",
+    "pre-chorus",
+    "bridge",
+    "intro",
+    "outro",
+    "hook",
+    "refrain",
+];
+
+/// Check each line's effective `song_part` (see
+/// [`SongPart::effective`](crate::SongPart::effective)) against
+/// [`STANDARD_SONG_PARTS`], case-insensitively. A line with no song-part at
+/// all is never flagged; one with a non-standard value is, with the closest
+/// standard value (by edit distance) suggested when it's close enough to
+/// plausibly be a typo (e.g. `"Chrous"` -> `"chorus"`).
+pub fn validate_song_parts(lines: &[LyricLine]) -> Vec<ValidationIssue> {
+    const TYPO_DISTANCE_THRESHOLD: usize = 2;
+
+    let mut issues = Vec::new();
+    for line in lines {
+        let Some(part) = line.song_part.effective() else {
+            continue;
+        };
+        let lower = part.to_lowercase();
+        if STANDARD_SONG_PARTS.contains(&lower.as_str()) {
+            continue;
+        }
+        let closest = STANDARD_SONG_PARTS
+            .iter()
+            .min_by_key(|standard| levenshtein_distance(&lower, standard))
+            .expect("STANDARD_SONG_PARTS is non-empty");
+        let message = if levenshtein_distance(&lower, closest) <= TYPO_DISTANCE_THRESHOLD {
+            format!("song-part `{part}` is not a standard value; did you mean `{closest}`?")
+        } else {
+            format!("song-part `{part}` is not a standard value")
+        };
+        issues.push(ValidationIssue { message });
+    }
+    issues
+}
+
+/// Check that every [`LyricLine::agent`] id actually appears in `agents`. A
+/// line with no agent at all is never flagged -- only a reference to an id
+/// that was never declared, which normally means the id was mistyped or the
+/// `<ttm:agent>` declaration was dropped somewhere in a recovery/edit tool
+/// that doesn't know to keep the two in sync.
+pub fn validate_agent_references(lines: &[LyricLine], agents: &[Agent]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for line in lines {
+        let Some(agent_id) = line.agent.as_deref() else {
+            continue;
+        };
+        if !agents.iter().any(|agent| agent.id == agent_id) {
+            issues.push(ValidationIssue {
+                message: format!("line references undeclared agent `{agent_id}`"),
+            });
+        }
+    }
+    issues
+}
+
+/// How far past `expected_duration_ms` a line's end is allowed to run before
+/// [`validate_track_duration`] flags it, to absorb the trailing silence most
+/// tracks have after their last lyric.
+const DURATION_OVERRUN_TOLERANCE_MS: u64 = 2000;
+
+/// Check that no line's [`LyricLine::end_ms`] runs more than
+/// [`DURATION_OVERRUN_TOLERANCE_MS`] past `expected_duration_ms` (typically
+/// [`ParsedSourceData::expected_duration_ms`](crate::ParsedSourceData::expected_duration_ms)),
+/// which usually means a mistimed line or a lyric file accidentally
+/// concatenated from a different, longer track.
+pub fn validate_track_duration(
+    lines: &[LyricLine],
+    expected_duration_ms: u64,
+) -> Vec<ValidationIssue> {
+    let Some(max_end_ms) = lines.iter().map(|line| line.end_ms).max() else {
+        return Vec::new();
+    };
+    if max_end_ms <= expected_duration_ms + DURATION_OVERRUN_TOLERANCE_MS {
+        return Vec::new();
+    }
+    vec![ValidationIssue {
+        message: format!(
+            "last line ends at {max_end_ms}ms, more than {DURATION_OVERRUN_TOLERANCE_MS}ms \
+             past the track's {expected_duration_ms}ms duration"
+        ),
+    }]
+}
+
+/// Check for a line that carries a translation or romanization but has no
+/// main-track content of its own -- every [`LyricLine::words`] entry missing
+/// or blank. This usually means a `<span ttm:role="x-translation">` (or
+/// `x-roman`) ended up as the line's only content because the main text was
+/// mis-tagged with the same role, or dropped entirely during a bad edit; a
+/// line that's genuinely wordless (e.g. an instrumental gap) never carries a
+/// translation in the first place, so this is a much stronger signal than
+/// [`ParsedSourceData::syllable_count`](crate::ParsedSourceData::syllable_count)
+/// being zero across the whole document.
+pub fn validate_translation_without_content(lines: &[LyricLine]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let has_content = line.words.iter().any(|word| !word.text.trim().is_empty());
+        if has_content {
+            continue;
+        }
+        if !line.translations.is_empty() {
+            issues.push(ValidationIssue {
+                message: format!(
+                    "line {index} has a translation but no main-track content -- likely a \
+                     mis-tagged span"
+                ),
+            });
+        } else if !line.romanizations.is_empty() {
+            issues.push(ValidationIssue {
+                message: format!(
+                    "line {index} has a romanization but no main-track content -- likely a \
+                     mis-tagged span"
+                ),
+            });
+        }
+    }
+    issues
+}
+
+/// Classic dynamic-programming edit distance between two strings, counting
+/// single-character insertions, deletions, and substitutions.
+pub fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut prev_diag = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let new_val = (row[j] + 1).min(row[j + 1] + 1).min(prev_diag + cost);
+            prev_diag = row[j + 1];
+            row[j + 1] = new_val;
+        }
+    }
+    row[b.len()]
+}
+
+fn reverse_word_timing(words: &mut [Word], total_ms: u64) {
+    words.reverse();
+    for word in words.iter_mut() {
+        let start = total_ms.saturating_sub(word.end_ms);
+        let end = total_ms.saturating_sub(word.start_ms);
+        word.start_ms = start;
+        word.end_ms = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SongPart;
+
+    fn line(start: u64, end: u64, agent: &str, text: &str) -> LyricLine {
+        let mut l = LyricLine::new(start, end);
+        l.agent = Some(agent.to_string());
+        l.words.push(Word {
+            start_ms: start,
+            end_ms: end,
+            text: text.to_string(),
+            lang: None,
+            furigana: None,
+        });
+        l
+    }
+
+    #[test]
+    fn merges_consecutive_short_lines_with_same_agent() {
+        let mut lines = vec![
+            line(0, 300, "v1", "a"),
+            line(300, 600, "v1", "b"),
+            line(600, 3000, "v1", "c"),
+        ];
+        merge_short_lines(&mut lines, 500);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].start_ms, 0);
+        assert_eq!(lines[0].end_ms, 600);
+        assert_eq!(lines[0].words.len(), 2);
+    }
+
+    #[test]
+    fn does_not_merge_across_different_agents() {
+        let mut lines = vec![line(0, 300, "v1", "a"), line(300, 600, "v2", "b")];
+        merge_short_lines(&mut lines, 500);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn does_not_merge_across_song_part_boundary() {
+        let mut a = line(0, 300, "v1", "a");
+        a.song_part = SongPart {
+            div: Some("verse".into()),
+            p: None,
+        };
+        let mut b = line(300, 600, "v1", "b");
+        b.song_part = SongPart {
+            div: Some("chorus".into()),
+            p: None,
+        };
+        let mut lines = vec![a, b];
+        merge_short_lines(&mut lines, 500);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn strip_annotations_clears_every_lines_translations_and_romanizations() {
+        let mut a = line(0, 1000, "v1", "a");
+        a.translations.push(AnnotatedText {
+            text: "hola".into(),
+            ..Default::default()
+        });
+        let mut b = line(1000, 2000, "v1", "b");
+        b.romanizations.push(AnnotatedText {
+            text: "b".into(),
+            ..Default::default()
+        });
+        let mut lines = vec![a, b];
+        strip_annotations(&mut lines);
+        assert!(lines[0].translations.is_empty());
+        assert!(lines[1].romanizations.is_empty());
+        assert_eq!(lines[0].words[0].text, "a");
+    }
+
+    #[test]
+    fn normalize_translation_languages_merges_equivalent_tags() {
+        let mut l = line(0, 1000, "v1", "a");
+        l.translations.push(AnnotatedText {
+            lang: Some("zh-CN".into()),
+            text: "简体".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        l.translations.push(AnnotatedText {
+            lang: Some("zh-Hans".into()),
+            text: "重复".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let mut lines = vec![l];
+        normalize_translation_languages(&mut lines, &LanguageEquivalenceTable::default_groups());
+        assert_eq!(lines[0].translations.len(), 1);
+        assert_eq!(lines[0].translations[0].text, "简体");
+    }
+
+    #[test]
+    fn normalize_translation_languages_is_a_noop_with_an_empty_table() {
+        let mut l = line(0, 1000, "v1", "a");
+        l.translations.push(AnnotatedText {
+            lang: Some("zh-CN".into()),
+            text: "简体".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        l.translations.push(AnnotatedText {
+            lang: Some("zh-Hans".into()),
+            text: "重复".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let mut lines = vec![l];
+        normalize_translation_languages(&mut lines, &LanguageEquivalenceTable::default());
+        assert_eq!(lines[0].translations.len(), 2);
+    }
+
+    #[test]
+    fn normalize_translation_languages_leaves_untagged_text_alone() {
+        let mut l = line(0, 1000, "v1", "a");
+        l.translations.push(AnnotatedText {
+            lang: None,
+            text: "first".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        l.translations.push(AnnotatedText {
+            lang: None,
+            text: "second".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        let mut lines = vec![l];
+        normalize_translation_languages(&mut lines, &LanguageEquivalenceTable::default_groups());
+        assert_eq!(lines[0].translations.len(), 2);
+    }
+
+    #[test]
+    fn distribute_evenly_splits_the_line_span_without_gaps_or_overlaps() {
+        let mut l = LyricLine::new(0, 900);
+        for text in ["a", "b", "c"] {
+            l.words.push(Word {
+                start_ms: 0,
+                end_ms: 0,
+                text: text.to_string(),
+                lang: None,
+                furigana: None,
+            });
+        }
+        let mut lines = vec![l];
+        distribute_evenly(&mut lines);
+        let words = &lines[0].words;
+        assert_eq!(words[0].start_ms, 0);
+        assert_eq!(words[0].end_ms, 300);
+        assert_eq!(words[1].start_ms, 300);
+        assert_eq!(words[1].end_ms, 600);
+        assert_eq!(words[2].start_ms, 600);
+        assert_eq!(words[2].end_ms, 900);
+    }
+
+    #[test]
+    fn distribute_evenly_leaves_lines_without_words_untouched() {
+        let mut lines = vec![LyricLine::new(0, 1000)];
+        distribute_evenly(&mut lines);
+        assert!(lines[0].words.is_empty());
+    }
+
+    #[test]
+    fn auto_word_splitting_on_hello_world_preserves_the_space_between_words() {
+        let word = Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "hello world".into(),
+            lang: None,
+            furigana: None,
+        };
+        let split = auto_split_word(&word);
+        let texts: Vec<&str> = split.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, vec!["hello", " ", "world"]);
+        // The space carries no duration of its own and sits exactly at the
+        // boundary between the two words it separates.
+        assert_eq!(split[1].start_ms, split[1].end_ms);
+        assert_eq!(split[0].end_ms, split[1].start_ms);
+        assert_eq!(split[1].end_ms, split[2].start_ms);
+        assert_eq!(split[2].end_ms, 1000);
+    }
+
+    #[test]
+    fn auto_split_word_hyphenates_within_a_single_word() {
+        let word = Word {
+            start_ms: 0,
+            end_ms: 400,
+            text: "hyphenation".into(),
+            lang: None,
+            furigana: None,
+        };
+        let split = auto_split_word(&word);
+        let texts: Vec<&str> = split.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, vec!["hy", "phen", "a", "tion"]);
+        assert_eq!(split[0].start_ms, 0);
+        assert_eq!(split.last().unwrap().end_ms, 400);
+    }
+
+    #[test]
+    fn auto_split_word_leaves_a_single_token_syllable_unchanged() {
+        let word = Word {
+            start_ms: 0,
+            end_ms: 200,
+            text: "la".into(),
+            lang: None,
+            furigana: None,
+        };
+        assert_eq!(auto_split_word(&word), vec![word]);
+    }
+
+    #[test]
+    fn auto_split_word_gives_punctuation_less_weight_than_letters() {
+        let word = Word {
+            start_ms: 0,
+            end_ms: 1100,
+            text: "hi!".into(),
+            lang: None,
+            furigana: None,
+        };
+        let split = auto_split_word(&word);
+        // "hi" (weight 2.0) vs "!" (weight 0.5): the letters should get
+        // roughly 4x the duration of the punctuation.
+        assert_eq!(split[0].end_ms - split[0].start_ms, 880);
+        assert_eq!(split[1].end_ms - split[1].start_ms, 220);
+    }
+
+    fn two_word_line() -> LyricLine {
+        let mut l = LyricLine::new(0, 1000);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "a".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 500,
+            end_ms: 1000,
+            text: "b".into(),
+            lang: None,
+            furigana: None,
+        });
+        l
+    }
+
+    #[test]
+    fn to_keyframes_samples_one_frame_per_fps_interval() {
+        let frames = to_keyframes(&[two_word_line()], 10.0);
+        // 1000ms of audio at 10fps -> frames at 0,100,...,1000ms = 11 frames.
+        assert_eq!(frames.len(), 11);
+    }
+
+    #[test]
+    fn to_keyframes_reports_progress_through_the_active_word() {
+        let frames = to_keyframes(&[two_word_line()], 4.0);
+        // At 4fps each frame is 250ms apart, so frame 1 lands at t=250ms,
+        // halfway through the first word (0..500ms).
+        let mid_first_word = &frames[1];
+        assert_eq!(mid_first_word.line_index, Some(0));
+        assert_eq!(mid_first_word.word_index, Some(0));
+        assert!((mid_first_word.progress - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn to_keyframes_reports_gaps_between_lines() {
+        let mut first = LyricLine::new(0, 500);
+        first.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "a".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut second = LyricLine::new(1000, 1500);
+        second.words.push(Word {
+            start_ms: 1000,
+            end_ms: 1500,
+            text: "b".into(),
+            lang: None,
+            furigana: None,
+        });
+        let frames = to_keyframes(&[first, second], 10.0);
+        // Frame 7 at 10fps is t=700ms, in the 500..1000ms gap between the
+        // two lines.
+        let gap_frame = &frames[7];
+        assert_eq!(gap_frame.line_index, None);
+        assert_eq!(gap_frame.word_index, None);
+    }
+
+    #[test]
+    fn remap_agents_renames_both_lines_and_the_agent_entry() {
+        let mut lines = vec![line(0, 1000, "v1", "a")];
+        let mut agents = vec![Agent {
+            id: "v1".into(),
+            name: None,
+            agent_type: crate::types::AgentType::Person,
+        }];
+        let mapping = HashMap::from([("v1".to_string(), "lead-singer".to_string())]);
+
+        remap_agents(&mut lines, &mut agents, &mapping);
+
+        assert_eq!(lines[0].agent.as_deref(), Some("lead-singer"));
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].id, "lead-singer");
+    }
+
+    #[test]
+    fn remap_agents_merges_into_an_existing_target_id_on_conflict() {
+        let mut lines = vec![line(0, 1000, "v2", "a")];
+        let mut agents = vec![
+            Agent {
+                id: "v2".into(),
+                name: None,
+                agent_type: crate::types::AgentType::Person,
+            },
+            Agent {
+                id: "lead-singer".into(),
+                name: Some("Alice".into()),
+                agent_type: crate::types::AgentType::Person,
+            },
+        ];
+        let mapping = HashMap::from([("v2".to_string(), "lead-singer".to_string())]);
+
+        remap_agents(&mut lines, &mut agents, &mapping);
+
+        assert_eq!(lines[0].agent.as_deref(), Some("lead-singer"));
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].name.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn remap_agents_leaves_unrelated_ids_untouched() {
+        let mut lines = vec![line(0, 1000, "v3", "a")];
+        let mut agents = vec![Agent {
+            id: "v3".into(),
+            name: None,
+            agent_type: crate::types::AgentType::Person,
+        }];
+        let mapping = HashMap::from([("v1".to_string(), "lead-singer".to_string())]);
+
+        remap_agents(&mut lines, &mut agents, &mapping);
+
+        assert_eq!(lines[0].agent.as_deref(), Some("v3"));
+        assert_eq!(agents[0].id, "v3");
+    }
+
+    #[test]
+    fn merge_repeated_syllables_unions_adjacent_identical_words() {
+        let mut l = LyricLine::new(0, 900);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 300,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 300,
+            end_ms: 600,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 600,
+            end_ms: 900,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut lines = vec![l];
+        merge_repeated_syllables(&mut lines);
+        assert_eq!(lines[0].words.len(), 1);
+        assert_eq!(lines[0].words[0].start_ms, 0);
+        assert_eq!(lines[0].words[0].end_ms, 900);
+        assert_eq!(lines[0].words[0].text, "あ");
+    }
+
+    #[test]
+    fn merge_repeated_syllables_leaves_different_text_alone() {
+        let mut l = LyricLine::new(0, 600);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 300,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 300,
+            end_ms: 600,
+            text: "い".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut lines = vec![l];
+        merge_repeated_syllables(&mut lines);
+        assert_eq!(lines[0].words.len(), 2);
+    }
+
+    #[test]
+    fn merge_repeated_syllables_does_not_bridge_a_timing_gap() {
+        let mut l = LyricLine::new(0, 700);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 300,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 400,
+            end_ms: 700,
+            text: "あ".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut lines = vec![l];
+        merge_repeated_syllables(&mut lines);
+        assert_eq!(lines[0].words.len(), 2);
+    }
+
+    #[test]
+    fn merge_repeated_syllables_does_not_merge_across_a_language_change() {
+        let mut l = LyricLine::new(0, 600);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 300,
+            text: "a".into(),
+            lang: Some("en".into()),
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 300,
+            end_ms: 600,
+            text: "a".into(),
+            lang: Some("fr".into()),
+            furigana: None,
+        });
+        let mut lines = vec![l];
+        merge_repeated_syllables(&mut lines);
+        assert_eq!(lines[0].words.len(), 2);
+    }
+
+    #[test]
+    fn reverse_timing_swaps_line_order_and_mirrors_each_line() {
+        let mut lines = vec![line(0, 300, "v1", "a"), line(300, 1000, "v1", "b")];
+        reverse_timing(&mut lines, 1000);
+        assert_eq!(lines[0].start_ms, 0);
+        assert_eq!(lines[0].end_ms, 700);
+        assert_eq!(lines[0].words[0].text, "b");
+        assert_eq!(lines[1].start_ms, 700);
+        assert_eq!(lines[1].end_ms, 1000);
+        assert_eq!(lines[1].words[0].text, "a");
+    }
+
+    #[test]
+    fn reverse_timing_keeps_words_within_a_line_monotonically_increasing() {
+        let mut l = LyricLine::new(0, 1000);
+        l.words.push(Word {
+            start_ms: 0,
+            end_ms: 400,
+            text: "a".into(),
+            lang: None,
+            furigana: None,
+        });
+        l.words.push(Word {
+            start_ms: 400,
+            end_ms: 1000,
+            text: "b".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut lines = vec![l];
+        reverse_timing(&mut lines, 1000);
+        assert_eq!(lines[0].words[0].text, "b");
+        assert_eq!(lines[0].words[0].start_ms, 0);
+        assert_eq!(lines[0].words[0].end_ms, 600);
+        assert_eq!(lines[0].words[1].text, "a");
+        assert_eq!(lines[0].words[1].start_ms, 600);
+        assert_eq!(lines[0].words[1].end_ms, 1000);
+    }
+
+    #[test]
+    fn ensure_translation_placeholder_adds_an_empty_translation_when_missing() {
+        let mut lines = vec![line(0, 1000, "v1", "a")];
+        ensure_translation_placeholder(&mut lines, "en");
+        assert_eq!(lines[0].translations.len(), 1);
+        assert_eq!(lines[0].translations[0].lang.as_deref(), Some("en"));
+        assert_eq!(lines[0].translations[0].text, "");
+    }
+
+    #[test]
+    fn ensure_translation_placeholder_leaves_an_existing_translation_alone() {
+        let mut lines = vec![line(0, 1000, "v1", "a")];
+        lines[0].translations.push(AnnotatedText {
+            lang: Some("en".into()),
+            text: "hello".into(),
+            scheme: None,
+            syllables: Vec::new(),
+        });
+        ensure_translation_placeholder(&mut lines, "en");
+        assert_eq!(lines[0].translations.len(), 1);
+        assert_eq!(lines[0].translations[0].text, "hello");
+    }
+
+    #[test]
+    fn standard_song_parts_do_not_warn() {
+        let mut l = LyricLine::new(0, 1000);
+        l.song_part = SongPart {
+            div: Some("Chorus".into()),
+            p: None,
+        };
+        assert!(validate_song_parts(&[l]).is_empty());
+    }
+
+    #[test]
+    fn a_line_without_a_song_part_is_never_flagged() {
+        assert!(validate_song_parts(&[LyricLine::new(0, 1000)]).is_empty());
+    }
+
+    #[test]
+    fn a_typo_suggests_the_closest_standard_value() {
+        let mut l = LyricLine::new(0, 1000);
+        l.song_part = SongPart {
+            div: Some("Chrous".into()),
+            p: None,
+        };
+        let issues = validate_song_parts(&[l]);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("did you mean `chorus`"));
+    }
+
+    #[test]
+    fn a_wildly_different_value_warns_without_a_suggestion() {
+        let mut l = LyricLine::new(0, 1000);
+        l.song_part = SongPart {
+            div: Some("spoken-word-interlude".into()),
+            p: None,
+        };
+        let issues = validate_song_parts(&[l]);
+        assert_eq!(issues.len(), 1);
+        assert!(!issues[0].message.contains("did you mean"));
+    }
+
+    #[test]
+    fn a_dangling_agent_reference_is_flagged() {
+        let lines = vec![line(0, 1000, "v3", "a")];
+        let agents = vec![Agent {
+            id: "v1".into(),
+            name: None,
+            agent_type: crate::types::AgentType::Person,
+        }];
+        let issues = validate_agent_references(&lines, &agents);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("v3"));
+    }
+
+    #[test]
+    fn a_declared_agent_reference_does_not_warn() {
+        let lines = vec![line(0, 1000, "v1", "a")];
+        let agents = vec![Agent {
+            id: "v1".into(),
+            name: None,
+            agent_type: crate::types::AgentType::Person,
+        }];
+        assert!(validate_agent_references(&lines, &agents).is_empty());
+    }
+
+    #[test]
+    fn a_line_without_an_agent_is_never_flagged() {
+        assert!(validate_agent_references(&[LyricLine::new(0, 1000)], &[]).is_empty());
+    }
+
+    #[test]
+    fn a_line_ending_well_within_the_track_duration_does_not_warn() {
+        let lines = vec![line(0, 60_000, "v1", "a")];
+        assert!(validate_track_duration(&lines, 120_000).is_empty());
+    }
+
+    #[test]
+    fn a_line_ending_past_the_duration_but_within_the_tolerance_does_not_warn() {
+        let lines = vec![line(0, 121_500, "v1", "a")];
+        assert!(validate_track_duration(&lines, 120_000).is_empty());
+    }
+
+    #[test]
+    fn a_line_ending_well_past_the_track_duration_is_flagged() {
+        let lines = vec![line(0, 130_000, "v1", "a")];
+        let issues = validate_track_duration(&lines, 120_000);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("130000"));
+    }
+
+    #[test]
+    fn a_line_with_a_translation_but_no_main_content_is_flagged() {
+        let mut l = LyricLine::new(0, 1000);
+        l.translations.push(crate::types::AnnotatedText {
+            text: "hola".into(),
+            ..Default::default()
+        });
+        let issues = validate_translation_without_content(&[l]);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("translation"));
+    }
+
+    #[test]
+    fn a_line_with_a_romanization_but_no_main_content_is_flagged() {
+        let mut l = LyricLine::new(0, 1000);
+        l.romanizations.push(crate::types::AnnotatedText {
+            text: "konnichiwa".into(),
+            ..Default::default()
+        });
+        let issues = validate_translation_without_content(&[l]);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("romanization"));
+    }
+
+    #[test]
+    fn a_line_with_both_a_translation_and_main_content_is_not_flagged() {
+        let mut l = line(0, 1000, "v1", "hello");
+        l.translations.push(crate::types::AnnotatedText {
+            text: "hola".into(),
+            ..Default::default()
+        });
+        assert!(validate_translation_without_content(&[l]).is_empty());
+    }
+
+    #[test]
+    fn a_wordless_line_with_no_translation_or_romanization_is_not_flagged() {
+        assert!(validate_translation_without_content(&[LyricLine::new(0, 1000)]).is_empty());
+    }
+
+    #[test]
+    fn collect_timings_visits_words_before_background_in_line_order() {
+        let mut first = line(0, 1000, "v1", "hello");
+        first.background.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "ooh".into(),
+            lang: None,
+            furigana: None,
+        });
+        let lines = vec![first, line(1000, 2000, "v1", "world")];
+
+        let timings = collect_timings(&lines);
+        assert_eq!(
+            timings
+                .iter()
+                .map(|t| (t.line_index, t.slot))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, TimingSlot::Word(0)),
+                (0, TimingSlot::Background(0)),
+                (1, TimingSlot::Word(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_timings_writes_edited_bounds_back_to_the_matching_syllable() {
+        let mut lines = vec![line(0, 1000, "v1", "hello")];
+        let mut timings = collect_timings(&lines);
+        timings[0].start_ms = 50;
+        timings[0].end_ms = 950;
+
+        apply_timings(&mut lines, &timings);
+
+        assert_eq!(lines[0].words[0].start_ms, 50);
+        assert_eq!(lines[0].words[0].end_ms, 950);
+    }
+
+    #[test]
+    fn apply_timings_ignores_a_reference_whose_syllable_no_longer_exists() {
+        let mut lines = vec![line(0, 1000, "v1", "hello")];
+        let stale = TimingRef {
+            line_index: 0,
+            slot: TimingSlot::Word(5),
+            start_ms: 0,
+            end_ms: 1,
+        };
+        apply_timings(&mut lines, &[stale]);
+        assert_eq!(lines[0].words[0].start_ms, 0);
+        assert_eq!(lines[0].words[0].end_ms, 1000);
+    }
+
+    #[test]
+    fn a_collect_then_apply_round_trip_is_a_no_op() {
+        let lines_before = vec![
+            line(0, 1000, "v1", "hello"),
+            line(1000, 2500, "v2", "world"),
+        ];
+        let mut lines_after = lines_before.clone();
+        let timings = collect_timings(&lines_before);
+        apply_timings(&mut lines_after, &timings);
+
+        assert_eq!(
+            lines_after
+                .iter()
+                .map(|l| (l.start_ms, l.end_ms, l.words[0].start_ms, l.words[0].end_ms))
+                .collect::<Vec<_>>(),
+            lines_before
+                .iter()
+                .map(|l| (l.start_ms, l.end_ms, l.words[0].start_ms, l.words[0].end_ms))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,3065 @@
+//! Parsing of Apple Music / AMLL compatible TTML documents into
+//! [`ParsedSourceData`].
+//!
+//! `<agent>`/`<meta>` handling is intentionally not scoped to "inside
+//! `<head>`": a document that emits a second `<metadata>` block after
+//! `<body>`/`<div>` (some tools do) is merged additively into the same
+//! [`ParsedSourceData::agents`]/[`ParsedSourceData::metadata`] rather than
+//! resetting anything already collected, regardless of where it appears.
+//!
+//! This parser doesn't reconstruct per-syllable `<ruby>`/`<rt>` furigana from
+//! a document that already has it -- [`Word::furigana`] exists for
+//! [`crate::align_furigana`] to populate from two independently parsed
+//! tracks, not for this parser to fill in while reading one document. The
+//! one `<rt>` shape this parser does understand is degenerate: a `<span>`
+//! whose only content is an `<rt>` with no base text, used by some Japanese
+//! TTML files to gloss an entire line's reading at once. That's treated as a
+//! line-level romanization rather than a word.
+//!
+//! With [`TtmlParsingOptions::preserve_unknown_metadata`] set, `<metadata>`
+//! children in a namespace we don't recognize are kept as flat
+//! `(qualified_name, text)` pairs in [`ParsedSourceData::raw_metadata`]
+//! rather than dropped; nested custom elements aren't supported, only a
+//! single element with plain text content.
+//!
+//! Syllable text carrying a leftover `[00:01.00]`- or `<00:01.00>`-shaped
+//! timestamp tag (common residue from a hasty LRC/QRC-to-TTML conversion) is
+//! always reported as a warning; with
+//! [`TtmlParsingOptions::strip_stray_timestamps`] set, the tag is also
+//! removed from the word's text.
+//!
+//! By default, runs of whitespace inside a `<span>`'s text are collapsed to
+//! a single space, since most TTML is pretty-printed and the extra
+//! indentation isn't meaningful. A span with `xml:space="preserve"` opts out
+//! and keeps its text verbatim, for lyrics that use intentional multi-space
+//! alignment.
+//!
+//! A `<div>`'s own `begin`/`end`, when present, is treated as authoritative
+//! for the section it covers: a contained `<p>` starting before or ending
+//! after those bounds is reported as a warning (a common copy-paste error
+//! when a line is moved into the wrong section), though the bounds
+//! themselves aren't stored on [`LyricLine`] or [`SongPart`].
+//!
+//! A `<p>`'s `itunes:key` lets a head-level
+//! `<amll:translation for="L1,L2" lang="...">` attach one translation to
+//! several lines at once (`for` is a comma-separated key list); matching
+//! happens after the whole document is parsed since translations typically
+//! come before the lines they refer to, and any key with no matching line
+//! is reported as a warning rather than silently dropped.
+//!
+//! A `<span>`'s own `xml:lang`, when present, is kept per-syllable on
+//! [`Word::lang`] instead of only being read at the line level, so a
+//! frontend can switch fonts mid-line for mixed-language word-by-word
+//! lyrics; the generator writes it back the same way.
+//!
+//! Warnings raised about a specific `<p>` (a lingering `end`, a stray
+//! timestamp tag, a div-bounds violation) are pushed onto both
+//! [`ParsedSourceData::warnings`] and that line's own
+//! [`LyricLine::warnings`], so a caller that wants to highlight the
+//! offending line doesn't have to re-parse the flat warning strings.
+//!
+//! A bare `<br/>` inside a `<span>`/`<rt>`/`<amll:translation>` becomes a
+//! single space in the accumulated text: `trim_text` already strips the
+//! whitespace-only text nodes on either side of it, so without this the
+//! words it separates would run together with no space at all.
+//!
+//! A `<span ttm:role="x-bg">` is a background vocal sharing the enclosing
+//! `<p>`'s line rather than a word of its own; it's collected into
+//! [`LyricLine::background`] instead of [`LyricLine::words`], and the
+//! generator writes it back as a single trailing span using the line's own
+//! begin/end regardless of whether the line is otherwise word- or
+//! line-timed.
+//!
+//! A `<![CDATA[...]]>` section anywhere a plain text node would otherwise
+//! appear (a syllable, a translation, an `<rt>` gloss, an unrecognized
+//! metadata element) is read the same as one, decoding it straight from
+//! UTF-8 rather than unescaping it -- CDATA content is never
+//! entity-escaped, which is usually the reason an exporter reaches for it
+//! in the first place (protecting a bare `&`).
+//!
+//! A `<span ttm:role="x-translation">` is an inline whole-line translation,
+//! collected into the same [`LyricLine::translations`] as a head-level
+//! `<amll:translation>`. A line carrying the same translation both ways
+//! (as [`TranslationLayout::Both`](crate::TranslationLayout::Both) writes
+//! it) only keeps one copy: the head-level pass is skipped for any
+//! `(lang, text)` pair already present from an inline span.
+//!
+//! A `begin`/`end` value typed with full-width digits or separators (e.g.
+//! `"０３：０８．００２"`, common IME residue) is normalized to ASCII
+//! before parsing rather than rejected, with an informational warning
+//! recorded so the submission can still be flagged for cleanup.
+//!
+//! A malformed or concatenated file with more than one `<head>`/`<metadata>`
+//! block has all of them merged into the same
+//! [`ParsedSourceData::agents`]/[`ParsedSourceData::metadata`] rather than
+//! the later block replacing the earlier one wholesale: a later `<ttm:agent>`
+//! or `<amll:meta>` that repeats an id/key already seen updates that entry
+//! in place instead of appending a duplicate.
+//!
+//! An `isrc` metadata value is checked, once the whole document has been
+//! parsed, against the `CC-XXX-YY-NNNNN` shape (12 alphanumerics once
+//! hyphens are removed); a mismatch is reported as a warning rather than a
+//! hard parse failure, since some legacy submissions carry an imperfect one.
+//!
+//! The root `<tt>`'s `itunes:timing` attribute is read into
+//! [`ParsedSourceData::is_line_timing_mode`] case-insensitively, so a
+//! document written by this crate's own generator (which capitalizes it as
+//! `"Word"`/`"Line"`) parses back the same as one that uses lowercase.
+//!
+//! Once the whole document is parsed, its highest `itunes:key` number is
+//! compared against the actual line count: a key far beyond what the line
+//! count would suggest usually means some lines never got a key, or a run
+//! of numbers was skipped, and is reported as a warning since it silently
+//! breaks head-level translation matching for the affected lines.
+//!
+//! [`parse_ttml_bytes`] accepts either plain or gzip-compressed TTML,
+//! detecting the latter by its magic number; decompression itself is behind
+//! this crate's `gzip` feature so callers that never see compressed input
+//! don't pay for the `flate2` dependency.
+//!
+//! A document with enough lines to plausibly be a real song, but whose
+//! latest end time is still under half a second, is reported as a warning:
+//! a common cause is a source that measures time in seconds being read as
+//! if it were milliseconds (or the reverse). It's a coarse, heuristic check
+//! and can't distinguish a genuinely tiny document from a mis-scaled one,
+//! but it catches a very common unit mistake.
+//!
+//! An `itunes:key` shared by more than one line is also reported as a
+//! warning, since it makes head-level translation matching ambiguous: a
+//! `<amll:translation for="...">` referencing that key would attach to
+//! every line that shares it instead of the one line it was meant for.
+//!
+//! With [`TtmlParsingOptions::strip_background_parentheses`] (on by
+//! default), a `ttm:role="x-bg"` span's surrounding `()`/`（）` are removed
+//! before its text is stored, since they usually just mark a syllable as
+//! background rather than being content the lyric intends to display.
+//!
+//! A `ttm:role="x-translation"` span can itself be word-timed: if it
+//! contains nested `<span begin=... end=...>` children rather than text of
+//! its own, each one becomes a syllable of
+//! [`AnnotatedText::syllables`](crate::AnnotatedText::syllables) instead of
+//! the whole span being collapsed into a single-syllable translation.
+//!
+//! A `<p>` with no `<span>` at all -- its text sitting directly inside the
+//! element -- is treated as a single line-timed word spanning the whole
+//! line, which lets a document mix line-timed and word-timed lines (a verse
+//! sung line by line, a chorus sung word by word) instead of forcing every
+//! line into whichever mode `itunes:timing` declares for the document as a
+//! whole; [`generate_ttml_inner`](crate::generate_ttml_inner) writes such a
+//! line back the same way, with no wrapping `<span>`.
+//!
+//! [`TtmlParsingOptions::line_mode_timed_ratio`] guards against a document
+//! whose `itunes:timing` attribute (or lack of one) disagrees with what its
+//! `<span>`s actually look like: if the fraction that carry both `begin` and
+//! `end` falls below the configured ratio, [`ParsedSourceData::is_line_timing_mode`]
+//! is forced to line-timed and a warning is recorded. Off by default (a
+//! ratio of `0.0` never triggers), since callers relying on the plain
+//! `itunes:timing` attribute shouldn't have it silently overridden.
+//!
+//! With [`TtmlParsingOptions::trailing_text_as_translation`] set, a word-timed
+//! `<p>` (one whose spans already produced at least one word) that also has
+//! plain text sitting after its last `</span>` -- some sources write a
+//! whole-line translation this way instead of wrapping it in its own
+//! `ttm:role="x-translation"` span -- has that trailing text collected as an
+//! untimed line-level translation instead of being silently dropped. Off by
+//! default, since a document that just has stray whitespace or a typo after
+//! its last span shouldn't have it reinterpreted as a translation.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::errors::ConvertError;
+use crate::types::{
+    Agent, AgentType, AnnotatedText, ContentType, LyricLine, ParsedSourceData, SongPart,
+    ValidationIssue, Word,
+};
+
+#[derive(Default)]
+struct PendingLine {
+    start_ms: u64,
+    end_ms: u64,
+    /// Whether `<p>` carried its own `begin` attribute, so
+    /// [`finalize_p_element`] can tell a genuinely-zero start apart from
+    /// `start_ms` just being its default value, and derive the real start
+    /// from the line's earliest syllable in the latter case.
+    has_explicit_begin: bool,
+    agent: Option<String>,
+    words: Vec<Word>,
+    div_song_part: Option<String>,
+    p_song_part: Option<String>,
+    romanizations: Vec<AnnotatedText>,
+    translations: Vec<AnnotatedText>,
+    background: Vec<Word>,
+    key: Option<String>,
+    region: Option<String>,
+    /// Warnings about this specific line, mirrored into
+    /// [`ParsedSourceData::warnings`] but also carried onto the finished
+    /// [`LyricLine`] so a caller can highlight the offending `<p>` directly.
+    warnings: Vec<String>,
+}
+
+/// A head-level `<amll:translation for="L1,L2" lang="...">text</amll:translation>`,
+/// collected while parsing and applied to every line whose `itunes:key`
+/// appears in `keys` once the whole document has been parsed (translations
+/// commonly precede the lines they refer to).
+struct PendingTranslation {
+    keys: Vec<String>,
+    lang: Option<String>,
+    text: String,
+}
+
+/// Options controlling how [`parse_ttml_with_options`] interprets a
+/// document, notably the frame/tick rates used to convert `Nf`/`Nt`
+/// timestamps emitted by some TTML authoring tools.
+#[derive(Debug, Clone)]
+pub struct TtmlParsingOptions {
+    /// Frames per second, used to convert `begin`/`end` values like `100f`.
+    pub frame_rate: f64,
+    /// Ticks per second, used to convert `begin`/`end` values like `12t`.
+    pub tick_rate: f64,
+    /// Collect `<metadata>` children this crate doesn't otherwise recognize
+    /// (i.e. not `<ttm:agent>`/`<amll:meta>`) into
+    /// [`ParsedSourceData::raw_metadata`] instead of silently dropping them.
+    /// Off by default since most callers only care about the metadata keys
+    /// this crate understands.
+    pub preserve_unknown_metadata: bool,
+    /// When a syllable's text looks like it still carries a leftover
+    /// timestamp tag from a crude format conversion (e.g. `[00:01.00]` or
+    /// `<00:01.00>`), remove the tag instead of just warning about it. Off
+    /// by default so callers can inspect
+    /// [`ParsedSourceData::warnings`](crate::ParsedSourceData::warnings)
+    /// before deciding to trust the automatic cleanup.
+    pub strip_stray_timestamps: bool,
+    /// Strip a single layer of surrounding parentheses -- ASCII `()` or
+    /// full-width `（）` -- from a `ttm:role="x-bg"` background vocal's text
+    /// (e.g. `(oooh)` becomes `oooh`), since many sources use them purely to
+    /// mark a syllable as background in the raw lyric text rather than as
+    /// meaningful punctuation. On by default to match existing behavior; set
+    /// to `false` for lyrics that use parentheses as genuine content.
+    pub strip_background_parentheses: bool,
+    /// If the fraction of `<span>`s inside `<p>` elements that carry both
+    /// `begin` and `end` falls below this ratio, [`ParsedSourceData::is_line_timing_mode`]
+    /// is forced to `Some(true)` and a warning is recorded, even if the
+    /// document (or a stray syllable) otherwise looks word-timed. A document
+    /// with hundreds of untimed spans and a single timed one is almost
+    /// always line-timed with one mistakenly-annotated syllable, not a
+    /// word-timed document missing timing on everything else.
+    ///
+    /// Defaults to `0.0`, which never overrides anything -- a document is
+    /// only ever treated as word-timed or line-timed based on its own
+    /// `itunes:timing` attribute, matching this crate's long-standing
+    /// behavior. Callers that want the safety net should set something like
+    /// `0.5`.
+    pub line_mode_timed_ratio: f64,
+    /// When a `<p>` has no `ttm:agent` of its own, give it the same agent as
+    /// the previous `<p>` in the same `<div>`, instead of leaving
+    /// [`LyricLine::agent`](crate::LyricLine::agent) `None`. Some sources
+    /// only label the agent on the first line of each speaker's turn and
+    /// leave it implicit on the lines that follow. Off by default, matching
+    /// this crate's long-standing behavior of leaving an unlabeled line's
+    /// agent unset; a `<div>` boundary always resets the inherited agent
+    /// rather than carrying it across into an unrelated section.
+    pub inherit_agent_within_div: bool,
+    /// When an XML syntax error or an element-level error (e.g. an
+    /// unparsable `<div begin=...>`) is hit partway through the document,
+    /// record a warning and skip ahead to the next `<p` instead of failing
+    /// the whole parse. A single malformed `<div>` then only costs what lies
+    /// between the error and the next `<p` (a `<p>` inside that same `<div>`
+    /// is still read) rather than every line after it in the document. Off by
+    /// default, matching this crate's long-standing behavior of surfacing
+    /// parse errors directly.
+    pub recover_from_errors: bool,
+    /// Treat plain text sitting after a word-timed `<p>`'s last `</span>` as
+    /// an untimed line-level translation, instead of silently dropping it.
+    /// Some sources write a whole-line translation as bare text rather than
+    /// wrapping it in its own `<span ttm:role="x-translation">`. Off by
+    /// default, since a document with stray whitespace or a typo trailing
+    /// its last span shouldn't have it reinterpreted as a translation.
+    pub trailing_text_as_translation: bool,
+}
+
+impl Default for TtmlParsingOptions {
+    fn default() -> Self {
+        Self {
+            frame_rate: 30.0,
+            tick_rate: 1000.0,
+            preserve_unknown_metadata: false,
+            strip_stray_timestamps: false,
+            strip_background_parentheses: true,
+            line_mode_timed_ratio: 0.0,
+            inherit_agent_within_div: false,
+            recover_from_errors: false,
+            trailing_text_as_translation: false,
+        }
+    }
+}
+
+struct ParserState {
+    data: ParsedSourceData,
+    current_p: Option<PendingLine>,
+    current_span_start: Option<u64>,
+    current_span_end: Option<u64>,
+    /// Set while the span currently being parsed had a `begin`/`end` written
+    /// as a `%` percentage rather than a supported time value, so the
+    /// closing handler can still keep its text instead of dropping it.
+    current_span_percent_time: bool,
+    /// `ttm:role` of the span currently being parsed, if any (e.g.
+    /// `"x-roman"` for an inline romanization span).
+    current_span_role: Option<String>,
+    /// `xml:scheme` of the span currently being parsed, if any.
+    current_span_scheme: Option<String>,
+    /// Whether the span currently being parsed had `xml:space="preserve"`,
+    /// in which case its text is kept verbatim instead of having internal
+    /// whitespace runs collapsed.
+    current_span_preserve_space: bool,
+    /// `xml:lang` of the span currently being parsed, if any (a syllable in
+    /// a different language than the rest of its line).
+    current_span_lang: Option<String>,
+    span_text: String,
+    /// Set while inside an `<rt>` element, so text events route into
+    /// `rt_text` instead of `span_text`.
+    in_rt: bool,
+    rt_text: String,
+    /// Nesting depth of `<metadata>` elements, so unrecognized children are
+    /// only collected as raw metadata when actually inside one.
+    metadata_depth: usize,
+    /// Qualified name (e.g. `"myns:bpm"`) of an unrecognized `<metadata>`
+    /// child currently being captured, if any.
+    pending_raw_key: Option<String>,
+    raw_text: String,
+    current_div_song_part: Option<String>,
+    /// Declared `begin`/`end` of the `<div>` currently being parsed, if any.
+    /// Used only to warn about a contained `<p>` falling outside this
+    /// range; not retained on the model.
+    current_div_start: Option<u64>,
+    current_div_end: Option<u64>,
+    /// The most recent explicit `ttm:agent` seen on a `<p>` within the
+    /// current `<div>`, used by [`TtmlParsingOptions::inherit_agent_within_div`]
+    /// to fill in a following `<p>` that has none of its own. Reset to `None`
+    /// at every `<div>` boundary.
+    current_div_last_agent: Option<String>,
+    options: TtmlParsingOptions,
+    /// When `false` (quick-validation mode), finished lines are counted but
+    /// discarded rather than appended to `data.lines`, so callers that only
+    /// want [`ParsedSourceData::warnings`] don't pay for retaining every
+    /// syllable's text and timing.
+    keep_lines: bool,
+    line_count: usize,
+    /// Set while inside an `<amll:translation>` element, so text events
+    /// route into `translation_text` instead of `span_text`.
+    in_translation: bool,
+    translation_text: String,
+    current_translation_keys: Vec<String>,
+    current_translation_lang: Option<String>,
+    pending_translations: Vec<PendingTranslation>,
+    /// Total `<span>`s seen inside a `<p>`, and how many of those carried
+    /// both `begin` and `end`; used by [`TtmlParsingOptions::line_mode_timed_ratio`]
+    /// to decide whether the document is really word-timed.
+    span_total_count: u64,
+    span_timed_count: u64,
+    /// Set while inside a `ttm:role="x-translation"` span but not yet
+    /// inside one of its nested per-syllable children, so a nested `<span>`
+    /// is recognized as a translation syllable rather than an unrelated
+    /// top-level span.
+    in_x_translation_span: bool,
+    /// Set while inside a syllable `<span>` nested directly inside an
+    /// `x-translation` span.
+    x_translation_child_active: bool,
+    /// Syllables collected so far for the `x-translation` span currently
+    /// being parsed, if it turns out to be word-timed.
+    x_translation_syllables: Vec<Word>,
+    /// The `xml:lang` of the `x-translation` span itself, kept separately
+    /// from `current_span_lang` since that field gets overwritten by each
+    /// nested syllable `<span>` as it's entered.
+    x_translation_lang: Option<String>,
+}
+
+impl ParserState {
+    fn new(options: TtmlParsingOptions) -> Self {
+        Self {
+            data: ParsedSourceData::default(),
+            current_p: None,
+            current_span_start: None,
+            current_span_end: None,
+            current_span_percent_time: false,
+            current_span_role: None,
+            current_span_scheme: None,
+            current_span_preserve_space: false,
+            current_span_lang: None,
+            span_text: String::new(),
+            in_rt: false,
+            rt_text: String::new(),
+            metadata_depth: 0,
+            pending_raw_key: None,
+            raw_text: String::new(),
+            current_div_song_part: None,
+            current_div_start: None,
+            current_div_end: None,
+            current_div_last_agent: None,
+            options,
+            keep_lines: true,
+            line_count: 0,
+            in_translation: false,
+            translation_text: String::new(),
+            current_translation_keys: Vec::new(),
+            current_translation_lang: None,
+            pending_translations: Vec::new(),
+            span_total_count: 0,
+            span_timed_count: 0,
+            in_x_translation_span: false,
+            x_translation_child_active: false,
+            x_translation_syllables: Vec::new(),
+            x_translation_lang: None,
+        }
+    }
+
+    /// Append a single space to whichever text buffer is currently active,
+    /// for `<br/>` handling: it stands in for the line break without
+    /// leaving a leading/trailing gap once the surrounding whitespace-only
+    /// text nodes have already been trimmed away.
+    fn push_break_space(&mut self) {
+        if self.pending_raw_key.is_some() {
+            self.raw_text.push(' ');
+        } else if self.in_translation {
+            self.translation_text.push(' ');
+        } else if self.in_rt {
+            self.rt_text.push(' ');
+        } else {
+            self.span_text.push(' ');
+        }
+    }
+
+    /// Parse a `begin`/`end` attribute value, recording an informational
+    /// warning if it needed full-width-to-ASCII normalization first.
+    fn parse_time_attr(&mut self, raw: &str) -> Result<u64, ConvertError> {
+        if normalize_fullwidth_time_chars(raw) != raw {
+            self.data.warnings.push(format!(
+                "timestamp `{raw}` uses full-width digits or separators; normalized to ASCII"
+            ));
+        }
+        parse_ttml_time(raw, &self.options)
+    }
+
+    /// Parse a `<span>`'s `begin`/`end` attribute value, warning instead of
+    /// erroring on a `%`-relative percentage -- some non-standard authoring
+    /// tools express syllable timing that way, but this parser has no
+    /// document duration to resolve it against.
+    fn parse_span_time_attr(&mut self, raw: &str) -> Result<Option<u64>, ConvertError> {
+        if raw.trim().ends_with('%') {
+            self.data.warnings.push(format!(
+                "span timestamp `{raw}` uses unsupported percentage-based timing; keeping its text without word-level timing"
+            ));
+            return Ok(None);
+        }
+        self.parse_time_attr(raw).map(Some)
+    }
+
+    fn handle_start(&mut self, local: &str, e: &BytesStart) -> Result<(), ConvertError> {
+        match local {
+            "tt" => {
+                for attr in e.attributes().flatten() {
+                    if local_name(attr.key.as_ref()) == "timing" {
+                        // Matched case-insensitively: the generator itself
+                        // writes the capitalized `"Word"`/`"Line"` form, and
+                        // a document round-tripped through it shouldn't be
+                        // treated as unrecognized.
+                        self.data.is_line_timing_mode =
+                            match attr_value(&attr)?.to_lowercase().as_str() {
+                                "line" => Some(true),
+                                "word" => Some(false),
+                                _ => None,
+                            };
+                    }
+                }
+            }
+            "div" => {
+                self.current_div_song_part = None;
+                self.current_div_start = None;
+                self.current_div_end = None;
+                self.current_div_last_agent = None;
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        "song-part" => self.current_div_song_part = Some(attr_value(&attr)?),
+                        "begin" => {
+                            self.current_div_start =
+                                Some(self.parse_time_attr(&attr_value(&attr)?)?)
+                        }
+                        "end" => {
+                            self.current_div_end = Some(self.parse_time_attr(&attr_value(&attr)?)?)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "p" => {
+                // A word-timed line always has at least one `<span>` inside
+                // it, so any bare text left over here belongs to a
+                // line-timed one written without span wrapping at all.
+                self.span_text.clear();
+                let mut line = PendingLine {
+                    div_song_part: self.current_div_song_part.clone(),
+                    ..PendingLine::default()
+                };
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        "begin" => {
+                            line.start_ms = self.parse_time_attr(&attr_value(&attr)?)?;
+                            line.has_explicit_begin = true;
+                        }
+                        "end" => line.end_ms = self.parse_time_attr(&attr_value(&attr)?)?,
+                        "agent" => line.agent = Some(attr_value(&attr)?),
+                        "song-part" => line.p_song_part = Some(attr_value(&attr)?),
+                        "key" => line.key = Some(attr_value(&attr)?),
+                        "region" => line.region = Some(attr_value(&attr)?),
+                        _ => {}
+                    }
+                }
+                if line.agent.is_some() {
+                    self.current_div_last_agent = line.agent.clone();
+                } else if self.options.inherit_agent_within_div {
+                    line.agent = self.current_div_last_agent.clone();
+                }
+                self.current_p = Some(line);
+            }
+            "span" => {
+                let mut start = None;
+                let mut end = None;
+                let mut role = None;
+                let mut scheme = None;
+                let mut preserve_space = false;
+                let mut lang = None;
+                let mut percent_time = false;
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        "begin" => {
+                            start = self.parse_span_time_attr(&attr_value(&attr)?)?;
+                            percent_time |= start.is_none();
+                        }
+                        "end" => {
+                            end = self.parse_span_time_attr(&attr_value(&attr)?)?;
+                            percent_time |= end.is_none();
+                        }
+                        // `ttm:role="x-roman"` marks an inline span as
+                        // carrying a romanization rather than the line's
+                        // main-track text; `xml:scheme` names the
+                        // romanization system used (e.g. "hepburn").
+                        "role" => role = Some(attr_value(&attr)?),
+                        "scheme" => scheme = Some(attr_value(&attr)?),
+                        // `xml:space="preserve"` keeps intentional
+                        // multi-space alignment or leading spaces in
+                        // artistic lyrics instead of having them collapsed.
+                        "space" => preserve_space = attr_value(&attr)? == "preserve",
+                        // A syllable-level `xml:lang`, for a word or two in a
+                        // different language than the rest of the line.
+                        "lang" => lang = Some(attr_value(&attr)?),
+                        _ => {}
+                    }
+                }
+                if self.in_x_translation_span && !self.x_translation_child_active {
+                    // A `<span begin=... end=...>` nested directly inside a
+                    // `ttm:role="x-translation"` span is one syllable of a
+                    // word-timed translation, assembled the same way the
+                    // line's own main-track syllables are.
+                    self.x_translation_child_active = true;
+                    self.current_span_start = start;
+                    self.current_span_end = end;
+                    self.current_span_lang = lang;
+                    self.span_text.clear();
+                    self.rt_text.clear();
+                } else {
+                    if self.current_p.is_some() {
+                        self.span_total_count += 1;
+                        if start.is_some() && end.is_some() {
+                            self.span_timed_count += 1;
+                        }
+                    }
+                    self.current_span_start = start;
+                    self.current_span_end = end;
+                    self.current_span_percent_time = percent_time;
+                    self.current_span_role = role.clone();
+                    self.current_span_scheme = scheme;
+                    self.current_span_preserve_space = preserve_space;
+                    self.current_span_lang = lang;
+                    self.span_text.clear();
+                    self.rt_text.clear();
+
+                    if role.as_deref() == Some("x-translation") {
+                        self.in_x_translation_span = true;
+                        self.x_translation_syllables.clear();
+                        self.x_translation_lang = self.current_span_lang.clone();
+                    }
+                }
+            }
+            "rt" => {
+                self.in_rt = true;
+            }
+            "br" => {
+                // `trim_text` strips the whitespace-only text nodes on
+                // either side of `<br/>`, so without this the surrounding
+                // words would be concatenated with no space at all; a
+                // single space is a reasonable stand-in for a line break
+                // once everything ends up on one `<p>`'s worth of text.
+                self.push_break_space();
+            }
+            "agent" => {
+                let mut id = None;
+                let mut agent_type = None;
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        // `xml:id` is the canonical way to name an agent,
+                        // but some authoring tools instead (or additionally)
+                        // write a bare `id`/`agent` attribute; accept either
+                        // as a fallback, preferring whichever comes first.
+                        "id" | "agent" if id.is_none() => id = Some(attr_value(&attr)?),
+                        "type" => {
+                            agent_type = Some(match attr_value(&attr)?.as_str() {
+                                "group" => AgentType::Group,
+                                _ => AgentType::Person,
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(id) = id {
+                    let agent_type = agent_type.unwrap_or_else(|| infer_agent_type(&id));
+                    // A malformed or concatenated file can carry more than
+                    // one `<metadata>` block; if a later one redeclares an
+                    // id already seen, treat it as an update rather than a
+                    // duplicate entry, but warn when the type actually
+                    // changes so a bad merge doesn't silently swap an
+                    // agent's type out from under its lines.
+                    if let Some(existing) = self.data.agents.iter_mut().find(|a| a.id == id) {
+                        if existing.agent_type != agent_type {
+                            self.data.warnings.push(format!(
+                                "agent `{id}` is redeclared with a different type ({:?} -> {:?}); keeping the later one",
+                                existing.agent_type, agent_type
+                            ));
+                        }
+                        existing.agent_type = agent_type;
+                    } else {
+                        self.data.agents.push(Agent {
+                            id,
+                            name: None,
+                            agent_type,
+                        });
+                    }
+                }
+            }
+            "meta" => {
+                // `<amll:meta key="..." value="..."/>` entries carry
+                // song-level metadata (title, artists, ISRC, ...).
+                let mut key = None;
+                let mut value = None;
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        "key" => key = Some(attr_value(&attr)?),
+                        "value" => value = Some(attr_value(&attr)?),
+                        _ => {}
+                    }
+                }
+                if let (Some(key), Some(value)) = (key, value) {
+                    // As with agents, a later `<metadata>` block redeclaring
+                    // the same key overwrites rather than duplicates it.
+                    if let Some(existing) = self.data.metadata.iter_mut().find(|(k, _)| *k == key) {
+                        existing.1 = value;
+                    } else {
+                        self.data.metadata.push((key, value));
+                    }
+                }
+            }
+            "metadata" => {
+                self.metadata_depth += 1;
+            }
+            "translation" => {
+                // `<amll:translation for="L1,L2" lang="...">text</amll:translation>`
+                // covers a translation shared by multiple lines (e.g. two
+                // lines translated as one sentence); `for` is a
+                // comma-separated list of the `itunes:key`s it applies to.
+                let mut keys = Vec::new();
+                let mut lang = None;
+                for attr in e.attributes().flatten() {
+                    match local_name(attr.key.as_ref()) {
+                        "for" => {
+                            keys = attr_value(&attr)?
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|key| !key.is_empty())
+                                .map(str::to_string)
+                                .collect();
+                        }
+                        "lang" => lang = Some(attr_value(&attr)?),
+                        _ => {}
+                    }
+                }
+                self.current_translation_keys = keys;
+                self.current_translation_lang = lang;
+                self.translation_text.clear();
+                self.in_translation = true;
+            }
+            _ => {
+                if self.metadata_depth > 0 && self.options.preserve_unknown_metadata {
+                    self.pending_raw_key =
+                        Some(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                    self.raw_text.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_end(&mut self, local: &str) {
+        match local {
+            "rt" => {
+                self.in_rt = false;
+            }
+            // Already handled in full by `handle_start`'s `push_break_space`
+            // call; nothing left to do once the (self-closing) element ends.
+            "br" => {}
+            "metadata" => {
+                self.metadata_depth = self.metadata_depth.saturating_sub(1);
+            }
+            "translation" => {
+                if !self.current_translation_keys.is_empty() {
+                    self.pending_translations.push(PendingTranslation {
+                        keys: std::mem::take(&mut self.current_translation_keys),
+                        lang: self.current_translation_lang.take(),
+                        text: self.translation_text.clone(),
+                    });
+                }
+                self.current_translation_keys.clear();
+                self.current_translation_lang = None;
+                self.in_translation = false;
+            }
+            "span" if self.x_translation_child_active => {
+                // Ending one syllable of a word-timed translation nested
+                // inside a `ttm:role="x-translation"` span.
+                self.x_translation_child_active = false;
+                let span_text = if self.current_span_preserve_space {
+                    self.span_text.clone()
+                } else {
+                    normalize_whitespace(&self.span_text)
+                };
+                if let (Some(start), Some(end)) = (self.current_span_start, self.current_span_end) {
+                    self.x_translation_syllables.push(Word {
+                        start_ms: start,
+                        end_ms: end,
+                        text: span_text,
+                        lang: self.current_span_lang.clone(),
+                        furigana: None,
+                    });
+                }
+                self.current_span_start = None;
+                self.current_span_end = None;
+                self.current_span_lang = None;
+            }
+            "span" => {
+                // An inline `ttm:role="x-roman"` span carries a whole-line
+                // romanization (with an optional `xml:scheme`) rather than
+                // main-track text, regardless of whether it has its own
+                // timing.
+                let span_text = if self.current_span_preserve_space {
+                    self.span_text.clone()
+                } else {
+                    normalize_whitespace(&self.span_text)
+                };
+                if self.current_span_role.as_deref() == Some("x-roman") {
+                    if let Some(line) = self.current_p.as_mut() {
+                        line.romanizations.push(AnnotatedText {
+                            lang: None,
+                            text: span_text,
+                            scheme: self.current_span_scheme.clone(),
+                            syllables: Vec::new(),
+                        });
+                    }
+                } else if self.current_span_role.as_deref() == Some("x-translation") {
+                    // An inline whole-line translation, kept alongside any
+                    // head-level `<amll:translation>` targeting the same
+                    // line; the two are deduplicated once the document is
+                    // fully parsed so a line that carries the same
+                    // translation both ways doesn't end up with it twice.
+                    //
+                    // If the translation was itself word-timed (its text
+                    // came from nested per-syllable `<span>`s rather than
+                    // being written directly inside this one), `text` joins
+                    // those syllables with spaces so plain-text consumers
+                    // still get something sensible, while `syllables` keeps
+                    // each one's own timing for consumers that want it.
+                    let syllables = std::mem::take(&mut self.x_translation_syllables);
+                    let text = if syllables.is_empty() {
+                        span_text
+                    } else {
+                        syllables
+                            .iter()
+                            .map(|word| word.text.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    };
+                    if let Some(line) = self.current_p.as_mut() {
+                        line.translations.push(AnnotatedText {
+                            lang: self.x_translation_lang.take(),
+                            text,
+                            scheme: None,
+                            syllables,
+                        });
+                    }
+                    self.in_x_translation_span = false;
+                } else if self.current_span_role.as_deref() == Some("x-bg") {
+                    // A background vocal syllable sharing this line rather
+                    // than main-track text; kept separate from `words` so
+                    // the generator can round-trip it as its own span.
+                    if has_mismatched_parentheses(&span_text) {
+                        let message = format!(
+                            "background text {span_text:?} opens and closes with mismatched half/full-width parentheses"
+                        );
+                        self.data.warnings.push(message.clone());
+                        if let Some(line) = self.current_p.as_mut() {
+                            line.warnings.push(message);
+                        }
+                    }
+                    let span_text = if self.options.strip_background_parentheses {
+                        strip_surrounding_parentheses(&span_text)
+                    } else {
+                        span_text
+                    };
+                    if let (Some(line), Some(start), Some(end)) = (
+                        self.current_p.as_mut(),
+                        self.current_span_start,
+                        self.current_span_end,
+                    ) {
+                        line.background.push(Word {
+                            start_ms: start,
+                            end_ms: end,
+                            text: span_text,
+                            lang: self.current_span_lang.clone(),
+                            furigana: None,
+                        });
+                    }
+                } else if let (Some(start), Some(end)) =
+                    (self.current_span_start, self.current_span_end)
+                {
+                    // Degenerate case: a `<span>` with an `<rt>` but no base
+                    // text is a whole-line reading (e.g. a furigana gloss
+                    // spanning the entire line), not a per-syllable ruby
+                    // annotation. We don't model per-syllable furigana at
+                    // all, so treat this one shape as a line-level
+                    // romanization instead of dropping it or emitting a
+                    // blank word.
+                    if self.span_text.is_empty() && !self.rt_text.is_empty() {
+                        if let Some(line) = self.current_p.as_mut() {
+                            line.romanizations.push(AnnotatedText {
+                                lang: None,
+                                text: self.rt_text.clone(),
+                                scheme: None,
+                                syllables: Vec::new(),
+                            });
+                        }
+                    } else {
+                        let (text, stray_count) =
+                            strip_stray_timestamps(&span_text, self.options.strip_stray_timestamps);
+                        if stray_count > 0 {
+                            let message = format!(
+                                "word text {span_text:?} looks like it has {stray_count} leftover timestamp tag(s){}",
+                                if self.options.strip_stray_timestamps {
+                                    "; stripped"
+                                } else {
+                                    ""
+                                }
+                            );
+                            self.data.warnings.push(message.clone());
+                            if let Some(line) = self.current_p.as_mut() {
+                                line.warnings.push(message);
+                            }
+                        }
+                        if let Some(line) = self.current_p.as_mut() {
+                            line.words.push(Word {
+                                start_ms: start,
+                                end_ms: end,
+                                text,
+                                lang: self.current_span_lang.clone(),
+                                furigana: None,
+                            });
+                        }
+                    }
+                } else if self.current_span_percent_time {
+                    // The `begin`/`end` couldn't be used (see
+                    // `parse_span_time_attr`), so fall back to the line's own
+                    // timing rather than losing the syllable's text.
+                    if let Some(line) = self.current_p.as_mut() {
+                        line.words.push(Word {
+                            start_ms: line.start_ms,
+                            end_ms: line.end_ms,
+                            text: span_text,
+                            lang: self.current_span_lang.clone(),
+                            furigana: None,
+                        });
+                    }
+                }
+                // Cleared here (rather than only at the next `<p>`/`<span>`
+                // start) so that `TtmlParsingOptions::trailing_text_as_translation`
+                // sees only the text after this span, not this span's own
+                // text prepended to it.
+                self.span_text.clear();
+                self.current_span_start = None;
+                self.current_span_end = None;
+                self.current_span_percent_time = false;
+                self.current_span_role = None;
+                self.current_span_scheme = None;
+                self.current_span_preserve_space = false;
+                self.current_span_lang = None;
+            }
+            "p" => {
+                if let Some(mut pending) = self.current_p.take() {
+                    if pending.words.is_empty() {
+                        let bare_text = normalize_whitespace(&self.span_text);
+                        if !bare_text.is_empty() {
+                            pending.words.push(Word {
+                                start_ms: pending.start_ms,
+                                end_ms: pending.end_ms,
+                                text: bare_text,
+                                lang: None,
+                                furigana: None,
+                            });
+                        }
+                    } else if self.options.trailing_text_as_translation {
+                        let trailing_text = normalize_whitespace(&self.span_text);
+                        if !trailing_text.is_empty() {
+                            pending.translations.push(AnnotatedText {
+                                lang: None,
+                                text: trailing_text,
+                                scheme: None,
+                                syllables: Vec::new(),
+                            });
+                        }
+                    }
+                    self.span_text.clear();
+                    let line_index = self.line_count;
+                    self.line_count += 1;
+                    let mut line = finalize_p_element(pending, line_index, &mut self.data.warnings);
+                    if let Some(div_start) = self.current_div_start {
+                        if line.start_ms < div_start {
+                            let message = format!(
+                                "line {line_index} begin={} starts before its div's declared begin={div_start}",
+                                line.start_ms
+                            );
+                            self.data.warnings.push(message.clone());
+                            line.warnings.push(message);
+                        }
+                    }
+                    if let Some(div_end) = self.current_div_end {
+                        if line.end_ms > div_end {
+                            let message = format!(
+                                "line {line_index} end={} ends after its div's declared end={div_end}",
+                                line.end_ms
+                            );
+                            self.data.warnings.push(message.clone());
+                            line.warnings.push(message);
+                        }
+                    }
+                    if self.keep_lines {
+                        self.data.lines.push(line);
+                    }
+                }
+            }
+            "div" => {
+                self.current_div_song_part = None;
+                self.current_div_start = None;
+                self.current_div_end = None;
+            }
+            _ => {
+                if let Some(key) = self.pending_raw_key.take() {
+                    self.data.raw_metadata.push((key, self.raw_text.clone()));
+                }
+            }
+        }
+    }
+
+    /// The per-event dispatch `run_parser`'s main loop drives, split out so
+    /// it can be wrapped in one place to recover from an error partway
+    /// through the document instead of failing the whole parse.
+    fn dispatch_event(&mut self, event: Event) -> Result<(), ConvertError> {
+        match event {
+            Event::Start(e) => {
+                let name = e.name();
+                let local = local_name(name.as_ref()).to_string();
+                self.handle_start(&local, &e)?;
+            }
+            // A self-closing element (`<span .../>`) never produces a
+            // separate `End` event, so it must run both halves here.
+            Event::Empty(e) => {
+                let name = e.name();
+                let local = local_name(name.as_ref()).to_string();
+                self.handle_start(&local, &e)?;
+                self.handle_end(&local);
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?;
+                if self.pending_raw_key.is_some() {
+                    self.raw_text.push_str(&text);
+                } else if self.in_translation {
+                    self.translation_text.push_str(&text);
+                } else if self.in_rt {
+                    self.rt_text.push_str(&text);
+                } else {
+                    self.span_text.push_str(&text);
+                }
+            }
+            // `<![CDATA[...]]>` content is never entity-escaped (that's the
+            // point of using it, usually to protect a bare `&`), so it's
+            // decoded straight from UTF-8 rather than run through
+            // `unescape`, but otherwise feeds the same text buffers as a
+            // plain `Event::Text`.
+            Event::CData(t) => {
+                let text = std::str::from_utf8(&t).map_err(|_| {
+                    ConvertError::Malformed("CDATA section is not valid UTF-8".to_string())
+                })?;
+                if self.pending_raw_key.is_some() {
+                    self.raw_text.push_str(text);
+                } else if self.in_translation {
+                    self.translation_text.push_str(text);
+                } else if self.in_rt {
+                    self.rt_text.push_str(text);
+                } else {
+                    self.span_text.push_str(text);
+                }
+            }
+            Event::End(e) => {
+                let name = e.name();
+                let local = local_name(name.as_ref()).to_string();
+                self.handle_end(&local);
+            }
+            // A leading `<?xml version="1.0" encoding="UTF-8"?>` declaration
+            // or a `<!DOCTYPE tt [...]>` (with or without an internal
+            // subset) is legal before the root element and carries nothing
+            // this parser needs; explicitly ignoring both here (rather than
+            // letting them fall into the catch-all below) documents that
+            // they were considered, not just missed.
+            Event::Decl(_) | Event::DocType(_) => {}
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// After an error partway through a `<div>`, discard whatever
+    /// line/div-level state was in progress so [`TtmlParsingOptions::recover_from_errors`]
+    /// resumes cleanly at the next `<p>` instead of attaching later
+    /// syllables to an abandoned line.
+    fn abandon_current_div(&mut self) {
+        self.current_p = None;
+        self.current_div_song_part = None;
+        self.current_div_start = None;
+        self.current_div_end = None;
+        self.current_div_last_agent = None;
+    }
+}
+
+/// After a recoverable error, scan forward from `byte_offset` in `input`
+/// for the next `<p` element start and build a fresh [`Reader`] positioned
+/// there, so a single malformed `<div>` doesn't discard every line that
+/// follows it in the document. Returns the new reader along with the
+/// absolute offset (into `input`) it now starts at -- a fresh [`Reader`]
+/// only knows positions relative to its own substring, so the caller needs
+/// this to correctly resync again if another error follows immediately.
+/// Returns `None` if no further `<p` exists.
+fn resync_to_next_p(input: &str, byte_offset: usize) -> Option<(Reader<&[u8]>, usize)> {
+    let byte_offset = byte_offset.min(input.len());
+    let tail = &input[byte_offset..];
+    let mut search_from = 0;
+    loop {
+        let idx = search_from + tail[search_from..].find("<p")?;
+        let after = idx + 2;
+        match tail.as_bytes().get(after) {
+            Some(b' ' | b'\t' | b'\n' | b'\r' | b'>' | b'/') => {
+                let mut reader = Reader::from_str(&tail[idx..]);
+                reader.config_mut().trim_text(true);
+                return Some((reader, byte_offset + idx));
+            }
+            _ => search_from = idx + 2,
+        }
+    }
+}
+
+/// Parse a TTML document into [`ParsedSourceData`] using the default
+/// [`TtmlParsingOptions`].
+pub fn parse_ttml(input: &str) -> Result<ParsedSourceData, ConvertError> {
+    parse_ttml_with_options(input, &TtmlParsingOptions::default())
+}
+
+/// Parse a TTML document into [`ParsedSourceData`], honoring `options`.
+pub fn parse_ttml_with_options(
+    input: &str,
+    options: &TtmlParsingOptions,
+) -> Result<ParsedSourceData, ConvertError> {
+    run_parser(input, options, true)
+}
+
+/// The two leading bytes of a gzip stream (RFC 1952), checked by
+/// [`parse_ttml_bytes`] to decide whether an input needs decompressing
+/// before it's valid TTML.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Parse `bytes` as TTML, transparently gzip-decompressing first if they
+/// start with the gzip magic number. For a bot that downloads a remote file
+/// without knowing ahead of time whether it fetched `some.ttml` or
+/// `some.ttml.gz`.
+///
+/// Decompression requires this crate's `gzip` feature; a non-gzip input
+/// costs nothing extra either way, and pays nothing at all when the feature
+/// is disabled. Gzip-magic bytes with the feature off are reported as
+/// [`ConvertError::Malformed`] rather than being fed to the XML parser as
+/// binary garbage.
+pub fn parse_ttml_bytes(bytes: &[u8]) -> Result<ParsedSourceData, ConvertError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        return parse_gzip_bytes(bytes);
+    }
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ConvertError::Malformed("input is not valid UTF-8".to_string()))?;
+    parse_ttml(text)
+}
+
+#[cfg(feature = "gzip")]
+fn parse_gzip_bytes(bytes: &[u8]) -> Result<ParsedSourceData, ConvertError> {
+    use std::io::Read;
+
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_string(&mut text)
+        .map_err(|err| ConvertError::Malformed(format!("failed to gunzip input: {err}")))?;
+    parse_ttml(&text)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn parse_gzip_bytes(_bytes: &[u8]) -> Result<ParsedSourceData, ConvertError> {
+    Err(ConvertError::Malformed(
+        "input looks gzip-compressed, but this build was compiled without the `gzip` feature"
+            .to_string(),
+    ))
+}
+
+/// Run the parser for its [`ParsedSourceData::warnings`] only, dropping
+/// each finished line instead of retaining it. Intended for callers (like
+/// a CI bot) that only need to know whether a document is clean and don't
+/// want the cost of cloning every syllable's text into a structure they'll
+/// throw away. The checks that run over the whole set of finished lines
+/// once parsing ends (key numbering, duplicate keys, timestamp magnitude,
+/// head-level translation keys with no matching line) see no lines here,
+/// so their warnings are not reported.
+pub fn validate_ttml_quick(
+    input: &str,
+    options: &TtmlParsingOptions,
+) -> Result<Vec<ValidationIssue>, ConvertError> {
+    Ok(run_parser(input, options, false)?
+        .warnings
+        .into_iter()
+        .map(|message| ValidationIssue { message })
+        .collect())
+}
+
+fn run_parser(
+    input: &str,
+    options: &TtmlParsingOptions,
+    keep_lines: bool,
+) -> Result<ParsedSourceData, ConvertError> {
+    let mut reader = Reader::from_str(input);
+    reader.config_mut().trim_text(true);
+
+    let mut state = ParserState::new(options.clone());
+    state.keep_lines = keep_lines;
+
+    // Absolute offset (into `input`) that `reader`'s own zero is anchored
+    // at; a reader rebuilt on a substring by `resync_to_next_p` only knows
+    // positions relative to itself, so this has to be tracked separately to
+    // resync correctly a second time if another error follows immediately.
+    let mut reader_base_offset: usize = 0;
+    let mut buf = Vec::new();
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(err) => {
+                if !options.recover_from_errors {
+                    return Err(err.into());
+                }
+                state.data.warnings.push(format!(
+                    "XML syntax error ({err}); skipping ahead to the next <p"
+                ));
+                let error_offset = reader_base_offset + reader.buffer_position() as usize;
+                match resync_to_next_p(input, error_offset) {
+                    Some((next_reader, new_base_offset)) => {
+                        reader = next_reader;
+                        reader_base_offset = new_base_offset;
+                        buf.clear();
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+        };
+        if matches!(event, Event::Eof) {
+            break;
+        }
+        if let Err(err) = state.dispatch_event(event) {
+            if !options.recover_from_errors {
+                return Err(err);
+            }
+            state.data.warnings.push(format!(
+                "error parsing document ({err}); skipping ahead to the next <p"
+            ));
+            state.abandon_current_div();
+            let error_offset = reader_base_offset + reader.buffer_position() as usize;
+            match resync_to_next_p(input, error_offset) {
+                Some((next_reader, new_base_offset)) => {
+                    reader = next_reader;
+                    reader_base_offset = new_base_offset;
+                    buf.clear();
+                    continue;
+                }
+                None => break,
+            }
+        }
+        buf.clear();
+    }
+
+    // Some documents reference a `ttm:agent` on a `<p>` without ever
+    // declaring it in `<head><metadata>`; synthesize one so callers always
+    // find an `Agent` for every id a line points at.
+    for id in state
+        .data
+        .lines
+        .iter()
+        .filter_map(|line| line.agent.as_deref())
+        .collect::<std::collections::BTreeSet<_>>()
+    {
+        if !state.data.agents.iter().any(|a| a.id == id) {
+            state.data.agents.push(Agent {
+                id: id.to_string(),
+                name: None,
+                agent_type: infer_agent_type(id),
+            });
+        }
+    }
+
+    // Translations commonly precede the lines they refer to (they live in
+    // `<head>`), so they're matched up against `itunes:key`s only once
+    // every line has been parsed, rather than while walking the document.
+    for translation in &state.pending_translations {
+        for line in &mut state.data.lines {
+            let Some(key) = line.key.as_deref() else {
+                continue;
+            };
+            let already_present = line.translations.iter().any(|existing| {
+                existing.lang == translation.lang && existing.text == translation.text
+            });
+            if translation.keys.iter().any(|k| k == key) && !already_present {
+                line.translations.push(AnnotatedText {
+                    lang: translation.lang.clone(),
+                    text: translation.text.clone(),
+                    scheme: None,
+                    syllables: Vec::new(),
+                });
+            }
+        }
+        if state.keep_lines {
+            for key in &translation.keys {
+                let exists = state
+                    .data
+                    .lines
+                    .iter()
+                    .any(|line| line.key.as_deref() == Some(key.as_str()));
+                if !exists {
+                    state.data.warnings.push(format!(
+                        "translation `for` references key `{key}`, which no line's itunes:key matches"
+                    ));
+                }
+            }
+        }
+    }
+
+    // Checked once at the end rather than as each `<amll:meta>` is parsed,
+    // since a later metadata block is allowed to overwrite an earlier
+    // `isrc` value and only the final one should be validated.
+    for (key, value) in &state.data.metadata {
+        if key == "isrc" && !is_valid_isrc(value) {
+            state.data.warnings.push(format!(
+                "isrc `{value}` does not match the CC-XXX-YY-NNNNN shape"
+            ));
+        }
+    }
+
+    if let Some(message) = diagnose_key_numbering(&state.data.lines) {
+        state.data.warnings.push(message);
+    }
+
+    if let Some(message) = diagnose_timestamp_magnitude(&state.data.lines) {
+        state.data.warnings.push(message);
+    }
+
+    state
+        .data
+        .warnings
+        .extend(diagnose_duplicate_keys(&state.data.lines));
+
+    if state.span_total_count > 0 {
+        let timed_ratio = state.span_timed_count as f64 / state.span_total_count as f64;
+        if timed_ratio < state.options.line_mode_timed_ratio {
+            state.data.is_line_timing_mode = Some(true);
+            state.data.warnings.push(format!(
+                "only {}/{} spans carry both begin and end (ratio {timed_ratio:.2} below the configured {:.2}); treating the document as line-timed",
+                state.span_timed_count, state.span_total_count, state.options.line_mode_timed_ratio
+            ));
+        }
+    }
+
+    Ok(state.data)
+}
+
+/// A max `itunes:key` number at least this many times the line count is
+/// reported as likely having skipped or missing keys, rather than every
+/// harmless off-by-one gap.
+const KEY_NUMBERING_GAP_RATIO: usize = 2;
+
+/// Flag a document whose highest `itunes:key` number (e.g. `L200`) is far
+/// beyond its actual line count, which usually means some lines were never
+/// given a key or a run of numbers was skipped. This matters because
+/// head-level translations (`<amll:translation for="...">`) are matched up
+/// to lines purely by key, so a missing or misnumbered key silently drops a
+/// translation instead of erroring.
+fn diagnose_key_numbering(lines: &[LyricLine]) -> Option<String> {
+    let total_lines = lines.len();
+    if total_lines == 0 {
+        return None;
+    }
+
+    let max_key_number = lines
+        .iter()
+        .filter_map(|line| line.key.as_deref())
+        .filter_map(parse_key_number)
+        .max()?;
+
+    if max_key_number >= total_lines * KEY_NUMBERING_GAP_RATIO {
+        return Some(format!(
+            "highest itunes:key is L{max_key_number} but the document only has {total_lines} lines; some lines may be missing a key or a range of numbers was skipped"
+        ));
+    }
+
+    None
+}
+
+/// Parse the numeric suffix of an `itunes:key` of the conventional `"L<n>"`
+/// shape, ignoring keys that don't follow it rather than erroring.
+fn parse_key_number(key: &str) -> Option<usize> {
+    key.strip_prefix('L')?.parse().ok()
+}
+
+/// A document needs at least this many lines before its overall timespan is
+/// checked for a plausible order of magnitude; a handful of lines could
+/// legitimately span under a second (a short intro chant, a sample clip),
+/// so this only fires once there's enough lyrics that a real song is
+/// almost certainly longer than [`SUSPICIOUSLY_LOW_MAX_END_MS`].
+const MIN_LINES_FOR_TIMESTAMP_MAGNITUDE_CHECK: usize = 10;
+
+/// A document with [`MIN_LINES_FOR_TIMESTAMP_MAGNITUDE_CHECK`] or more lines
+/// whose latest end time is still under this many milliseconds is almost
+/// certainly using the wrong unit somewhere upstream.
+const SUSPICIOUSLY_LOW_MAX_END_MS: u64 = 500;
+
+/// Flag a document whose lines all end implausibly early, which usually
+/// means a source that measures time in seconds was fed in as if it were
+/// milliseconds (or the reverse: milliseconds truncated down to seconds).
+/// This is a coarse heuristic -- it can't tell a genuinely short document
+/// from a mis-scaled one on line count alone -- but a several-line-long
+/// lyric that supposedly ends before a second has passed is a very common
+/// shape for that specific mistake.
+fn diagnose_timestamp_magnitude(lines: &[LyricLine]) -> Option<String> {
+    if lines.len() < MIN_LINES_FOR_TIMESTAMP_MAGNITUDE_CHECK {
+        return None;
+    }
+
+    let max_end_ms = lines.iter().map(|line| line.end_ms).max().unwrap_or(0);
+    if max_end_ms == 0 || max_end_ms >= SUSPICIOUSLY_LOW_MAX_END_MS {
+        return None;
+    }
+
+    Some(format!(
+        "document has {} lines but its latest end time is only {max_end_ms}ms; timestamps may have been written in the wrong unit (e.g. seconds mistaken for milliseconds, or vice versa)",
+        lines.len()
+    ))
+}
+
+/// Flag every `itunes:key` shared by more than one line. A duplicate key is
+/// worse than a missing one: [`diagnose_key_numbering`] can only notice a
+/// key that was skipped, but a repeated key silently attaches a head-level
+/// translation to every line that shares it instead of just the one it was
+/// meant for.
+fn diagnose_duplicate_keys(lines: &[LyricLine]) -> Vec<String> {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut duplicates: Vec<&str> = Vec::new();
+    for key in lines.iter().filter_map(|line| line.key.as_deref()) {
+        if seen.contains(&key) {
+            if !duplicates.contains(&key) {
+                duplicates.push(key);
+            }
+        } else {
+            seen.push(key);
+        }
+    }
+    duplicates
+        .into_iter()
+        .map(|key| format!("itunes:key `{key}` is used by more than one line"))
+        .collect()
+}
+
+/// Names commonly used for a group/chorus `ttm:agent`, checked
+/// case-insensitively against the agent id when no explicit `type` is
+/// given.
+const CHORUS_AGENT_NAMES: &[&str] = &["合", "合唱", "合声", "chorus", "group"];
+
+/// Infer whether an agent id looks like it refers to a group/chorus rather
+/// than a single performer.
+fn infer_agent_type(id: &str) -> AgentType {
+    if CHORUS_AGENT_NAMES
+        .iter()
+        .any(|name| id.eq_ignore_ascii_case(name))
+    {
+        AgentType::Group
+    } else {
+        AgentType::Person
+    }
+}
+
+/// A syllable `end` extending the line `end` by more than this is reported
+/// as a warning; smaller corrections are common rounding noise.
+const LINE_END_DRIFT_WARNING_THRESHOLD_MS: u64 = 20;
+
+/// A word-timed line whose declared `end` lingers past its last syllable
+/// end by more than this is reported as a warning; it usually means a typo
+/// in `<p end=...>` rather than an intentional trailing pause.
+const LINGERING_LINE_END_WARNING_THRESHOLD_MS: u64 = 10_000;
+
+/// Collapse runs of whitespace in `text` down to a single space each,
+/// matching XML's default `xml:space="default"` handling of pretty-printed
+/// markup.
+fn normalize_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_was_space = false;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !last_was_space {
+                result.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            result.push(ch);
+            last_was_space = false;
+        }
+    }
+    result
+}
+
+/// True if `text` (trimmed) opens with one width of parenthesis and closes
+/// with the other, e.g. `(oooh）` -- an authoring inconsistency that
+/// [`strip_surrounding_parentheses`] intentionally leaves alone rather than
+/// guessing which width was intended.
+fn has_mismatched_parentheses(text: &str) -> bool {
+    let trimmed = text.trim();
+    let opens_ascii = trimmed.starts_with('(');
+    let opens_full = trimmed.starts_with('（');
+    let closes_ascii = trimmed.ends_with(')');
+    let closes_full = trimmed.ends_with('）');
+    (opens_ascii && closes_full) || (opens_full && closes_ascii)
+}
+
+/// Strip a single layer of surrounding ASCII `()` or full-width `（）`
+/// parentheses from `text`, if both are present, otherwise return it
+/// unchanged. Used for `ttm:role="x-bg"` background vocal text like
+/// `(oooh)`, where the parentheses mark it as background rather than being
+/// meaningful punctuation.
+fn strip_surrounding_parentheses(text: &str) -> String {
+    let trimmed = text.trim();
+    let stripped = trimmed
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .or_else(|| {
+            trimmed
+                .strip_prefix('（')
+                .and_then(|s| s.strip_suffix('）'))
+        });
+    stripped.unwrap_or(text).to_string()
+}
+
+/// Find `[00:01.00]`- or `<00:01.00>`-shaped timestamp tags in `text` --
+/// leftover residue from a crude LRC/QRC-to-TTML conversion -- and, when
+/// `strip` is set, remove them. Always returns how many tags were found, so
+/// the caller can warn even when not stripping.
+fn strip_stray_timestamps(text: &str, strip: bool) -> (String, usize) {
+    let ranges = find_stray_timestamp_ranges(text);
+    if ranges.is_empty() {
+        return (text.to_string(), 0);
+    }
+    if !strip {
+        return (text.to_string(), ranges.len());
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in &ranges {
+        result.push_str(&text[last..range.start]);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+    (result, ranges.len())
+}
+
+/// Byte ranges of `[MM:SS.ss]`/`<MM:SS.ss>`-shaped timestamp tags in `text`.
+fn find_stray_timestamp_ranges(text: &str) -> Vec<std::ops::Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let open = bytes[i];
+        let close = match open {
+            b'[' => b']',
+            b'<' => b'>',
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        match match_timestamp_body(bytes, i + 1, close) {
+            Some(end) => {
+                ranges.push(i..end + 1);
+                i = end + 1;
+            }
+            None => i += 1,
+        }
+    }
+    ranges
+}
+
+/// Matches a `MM:SS(.ss)?` body starting at `start`, returning the index of
+/// the closing bracket if the whole shape (digits, colon, digits, optional
+/// fractional part, closing bracket) is present.
+fn match_timestamp_body(bytes: &[u8], start: usize, close: u8) -> Option<usize> {
+    let mut j = start;
+    if consume_digits(bytes, &mut j) == 0 {
+        return None;
+    }
+    if bytes.get(j) != Some(&b':') {
+        return None;
+    }
+    j += 1;
+    if consume_digits(bytes, &mut j) == 0 {
+        return None;
+    }
+    if bytes.get(j) == Some(&b'.') {
+        j += 1;
+        if consume_digits(bytes, &mut j) == 0 {
+            return None;
+        }
+    }
+    (bytes.get(j) == Some(&close)).then_some(j)
+}
+
+fn consume_digits(bytes: &[u8], j: &mut usize) -> usize {
+    let start = *j;
+    while bytes.get(*j).is_some_and(u8::is_ascii_digit) {
+        *j += 1;
+    }
+    *j - start
+}
+
+/// Turn a [`PendingLine`] accumulated while parsing a `<p>` element into a
+/// finished [`LyricLine`], correcting the declared line `end` if a syllable
+/// ends later than it. Reports a warning when the correction is large
+/// enough to suggest the line's `end` was mistimed rather than just
+/// rounded, and another when a word-timed line's `end` lingers far past its
+/// last syllable.
+fn finalize_p_element(
+    mut pending: PendingLine,
+    line_index: usize,
+    warnings: &mut Vec<String>,
+) -> LyricLine {
+    if !pending.has_explicit_begin {
+        if let Some(min_word_start) = pending.words.iter().map(|w| w.start_ms).min() {
+            pending.start_ms = min_word_start;
+        }
+    }
+
+    let max_word_end = pending.words.iter().map(|w| w.end_ms).max().unwrap_or(0);
+    let end_ms = pending.end_ms.max(max_word_end);
+
+    if end_ms - pending.end_ms > LINE_END_DRIFT_WARNING_THRESHOLD_MS {
+        let message = format!(
+            "line begin={} end={} was extended to {} to cover a later syllable end",
+            pending.start_ms, pending.end_ms, end_ms
+        );
+        warnings.push(message.clone());
+        pending.warnings.push(message);
+    }
+
+    let is_word_timed = pending.words.len() > 1;
+    let lingering_gap = end_ms.saturating_sub(max_word_end);
+    if is_word_timed && lingering_gap > LINGERING_LINE_END_WARNING_THRESHOLD_MS {
+        let message = format!(
+            "line {line_index} end={end_ms} lingers {lingering_gap}ms past its last syllable end {max_word_end}"
+        );
+        warnings.push(message.clone());
+        pending.warnings.push(message);
+    }
+
+    if let Some(message) = detect_concatenated_main_tracks(line_index, &pending.words) {
+        warnings.push(message.clone());
+        pending.warnings.push(message);
+    }
+
+    LyricLine {
+        start_ms: pending.start_ms,
+        end_ms,
+        words: pending.words,
+        agent: pending.agent,
+        content_type: ContentType::Main,
+        song_part: SongPart {
+            div: pending.div_song_part,
+            p: pending.p_song_part,
+        },
+        translations: pending.translations,
+        romanizations: pending.romanizations,
+        background: pending.background,
+        key: pending.key,
+        region: pending.region,
+        warnings: pending.warnings,
+    }
+}
+
+/// A single main track's syllables always start at non-decreasing times. A
+/// syllable that starts earlier than one already seen in the same `<p>` is a
+/// sign that two parallel main-language tracks (e.g. a bilingual original)
+/// were concatenated into this line's `words` instead of one -- there's no
+/// second `words`-like field to keep them apart in, so [`finalize_p_element`]
+/// keeps them merged in appearance order, and this just reports that it
+/// happened rather than silently producing a line whose syllables jump
+/// backwards in time.
+fn detect_concatenated_main_tracks(line_index: usize, words: &[Word]) -> Option<String> {
+    let mut max_start_so_far = None;
+    for (position, word) in words.iter().enumerate() {
+        if let Some(max_start) = max_start_so_far {
+            if word.start_ms < max_start {
+                return Some(format!(
+                    "line {line_index} word {position} ({:?}) starts at {}ms, earlier than an already-seen syllable at {}ms -- looks like two main-track lyric lines were concatenated into one <p>; kept merged in appearance order",
+                    word.text, word.start_ms, max_start
+                ));
+            }
+        }
+        max_start_so_far =
+            Some(max_start_so_far.map_or(word.start_ms, |m: u64| m.max(word.start_ms)));
+    }
+    None
+}
+
+/// Parse a TTML time expression into milliseconds. Accepts colon-separated
+/// clock times (`"01:02:03.456"`, `"02:03.456"`) as well as single-value
+/// offset times with a unit suffix: `"1.5s"`, `"250ms"`, `"100f"` (frames,
+/// converted using `options.frame_rate`) and `"12t"` (ticks, converted
+/// using `options.tick_rate`).
+///
+/// Full-width digits and the full-width `：`/`．` separators (common IME
+/// residue, e.g. `"０３：０８．００２"`) are normalized to ASCII before
+/// parsing.
+pub fn parse_ttml_time(s: &str, options: &TtmlParsingOptions) -> Result<u64, ConvertError> {
+    let normalized = normalize_fullwidth_time_chars(s.trim());
+    let s = normalized.trim();
+    if s.contains(':') {
+        return parse_clock_time(s);
+    }
+    parse_offset_time(s, options)
+}
+
+/// Maps full-width digits (`０`-`９`) and the full-width `：`/`．`
+/// separators to their ASCII equivalents, leaving everything else alone.
+fn normalize_fullwidth_time_chars(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+            '：' => ':',
+            '．' => '.',
+            other => other,
+        })
+        .collect()
+}
+
+fn parse_clock_time(s: &str) -> Result<u64, ConvertError> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let (h, m, rest) = match parts.as_slice() {
+        [h, m, rest] => (h.parse::<u64>().ok(), m.parse::<u64>().ok(), *rest),
+        [m, rest] => (Some(0), m.parse::<u64>().ok(), *rest),
+        _ => (None, None, ""),
+    };
+    let (h, m) = match (h, m) {
+        (Some(h), Some(m)) => (h, m),
+        _ => return Err(ConvertError::InvalidTimestamp(s.to_string())),
+    };
+
+    let (sec_str, ms_str) = rest
+        .split_once('.')
+        .ok_or_else(|| ConvertError::InvalidTimestamp(s.to_string()))?;
+    let sec: u64 = sec_str
+        .parse()
+        .map_err(|_| ConvertError::InvalidTimestamp(s.to_string()))?;
+    // A fraction longer than milliseconds precision (3 digits) is only kept
+    // if the extra digits are all zero, i.e. truncating them loses nothing
+    // (".0500" -> ".050"); anything else would silently drop precision the
+    // source actually meant, so it's rejected instead of guessed at.
+    let ms_str = if ms_str.len() > 3 {
+        let (head, tail) = ms_str.split_at(3);
+        if tail.bytes().all(|b| b == b'0') {
+            head.to_string()
+        } else {
+            return Err(ConvertError::InvalidTimestamp(s.to_string()));
+        }
+    } else {
+        format!("{ms_str:0<3}")
+    };
+    let ms: u64 = ms_str
+        .parse()
+        .map_err(|_| ConvertError::InvalidTimestamp(s.to_string()))?;
+
+    Ok(((h * 60 + m) * 60 + sec) * 1000 + ms)
+}
+
+/// Suffixes checked longest-first so `"ms"` isn't mistaken for `"s"`.
+const OFFSET_TIME_UNITS: &[&str] = &["ms", "h", "m", "s", "f", "t"];
+
+fn parse_offset_time(s: &str, options: &TtmlParsingOptions) -> Result<u64, ConvertError> {
+    let unit = OFFSET_TIME_UNITS
+        .iter()
+        .find(|unit| s.ends_with(*unit))
+        .ok_or_else(|| ConvertError::InvalidTimestamp(s.to_string()))?;
+    let value_str = &s[..s.len() - unit.len()];
+    let value: f64 = value_str
+        .parse()
+        .map_err(|_| ConvertError::InvalidTimestamp(s.to_string()))?;
+
+    let ms = match *unit {
+        "h" => value * 3_600_000.0,
+        "m" => value * 60_000.0,
+        "s" => value * 1000.0,
+        "ms" => value,
+        "f" => value / options.frame_rate * 1000.0,
+        "t" => value / options.tick_rate * 1000.0,
+        _ => unreachable!(),
+    };
+    Ok(ms.round() as u64)
+}
+
+fn local_name(qname: &[u8]) -> &str {
+    let s = std::str::from_utf8(qname).unwrap_or("");
+    s.rsplit(':').next().unwrap_or(s)
+}
+
+fn attr_value(attr: &quick_xml::events::attributes::Attribute) -> Result<String, ConvertError> {
+    Ok(attr.unescape_value()?.into_owned())
+}
+
+/// Whether `value` matches the ISRC shape (`CC-XXX-YY-NNNNN`, or the same 12
+/// alphanumerics without the hyphens): a 2-letter country code, a 3-character
+/// registrant code, a 2-digit year, and a 5-digit designation code.
+fn is_valid_isrc(value: &str) -> bool {
+    let stripped: String = value.chars().filter(|c| *c != '-').collect();
+    stripped.len() == 12
+        && stripped[..2].chars().all(|c| c.is_ascii_alphabetic())
+        && stripped[2..5].chars().all(|c| c.is_ascii_alphanumeric())
+        && stripped[5..12].chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_words_and_line_bounds() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000" ttm:agent="v1">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines.len(), 1);
+        let line = &data.lines[0];
+        assert_eq!(line.start_ms, 0);
+        assert_eq!(line.end_ms, 2000);
+        assert_eq!(line.agent.as_deref(), Some("v1"));
+        assert_eq!(line.words.len(), 2);
+        assert_eq!(line.words[0].text, "hello");
+    }
+
+    #[test]
+    fn cdata_wrapped_syllable_text_is_kept_including_a_bare_ampersand() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000"><![CDATA[Rock & Roll]]></span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "Rock & Roll");
+    }
+
+    #[test]
+    fn a_p_with_no_spans_becomes_a_single_line_timed_word() {
+        let ttml = r#"<tt itunes:timing="Word"><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">line-timed verse</p>
+            <p begin="00:00:02.000" end="00:00:04.000">
+                <span begin="00:00:02.000" end="00:00:03.000">word</span>
+                <span begin="00:00:03.000" end="00:00:04.000">timed</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines.len(), 2);
+        assert_eq!(data.lines[0].words.len(), 1);
+        assert_eq!(data.lines[0].words[0].text, "line-timed verse");
+        assert_eq!(data.lines[0].words[0].start_ms, 0);
+        assert_eq!(data.lines[0].words[0].end_ms, 2000);
+        assert_eq!(data.lines[1].words.len(), 2);
+    }
+
+    #[test]
+    fn line_end_is_extended_to_cover_last_syllable() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.500">late</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].end_ms, 1500);
+        assert_eq!(data.warnings.len(), 1);
+        assert!(data.warnings[0].contains("extended"));
+    }
+
+    #[test]
+    fn a_p_with_no_begin_derives_its_start_from_the_earliest_syllable() {
+        let ttml = r#"<tt><body><div>
+            <p end="00:00:02.000">
+                <span begin="00:00:01.000" end="00:00:01.500">hi</span>
+                <span begin="00:00:01.500" end="00:00:02.000">there</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].start_ms, 1000);
+        assert_eq!(data.lines[0].end_ms, 2000);
+    }
+
+    #[test]
+    fn tiny_line_end_drift_does_not_warn() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.010">close</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].end_ms, 1010);
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn normal_line_end_does_not_warn_about_lingering() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn phantom_line_end_warns_about_lingering_past_last_syllable() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:32.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.warnings.len(), 1);
+        assert!(data.warnings[0].contains("lingers"));
+        assert!(data.warnings[0].contains("line 0"));
+        assert_eq!(data.lines[0].warnings, data.warnings);
+    }
+
+    #[test]
+    fn two_concatenated_main_tracks_are_merged_with_a_warning() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:01.000">こんにちは</span>
+                <span begin="00:00:01.000" end="00:00:02.000">世界</span>
+                <span begin="00:00:00.000" end="00:00:01.000">안녕</span>
+                <span begin="00:00:01.000" end="00:00:02.000">세상</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(
+            data.lines[0]
+                .words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["こんにちは", "世界", "안녕", "세상"]
+        );
+        assert_eq!(data.warnings.len(), 1);
+        assert!(data.warnings[0].contains("concatenated"));
+        assert_eq!(data.lines[0].warnings, data.warnings);
+    }
+
+    #[test]
+    fn a_single_main_track_never_warns_about_concatenation() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn line_warnings_stay_empty_for_a_clean_line() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+        assert!(data.lines[0].warnings.is_empty());
+    }
+
+    #[test]
+    fn div_bounds_warning_is_attached_to_the_offending_line() {
+        let ttml = r#"<tt><body><div begin="00:00:05.000" end="00:00:10.000">
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].warnings.len(), 1);
+        assert!(data.lines[0].warnings[0].contains("starts before"));
+    }
+
+    #[test]
+    fn parses_documents_missing_namespace_declarations() {
+        // Some minimal exporters emit `<tt>` without declaring any xmlns,
+        // relying on tag/attribute local names alone.
+        let ttml = r#"<tt>
+            <head><metadata>
+                <agent id="v1"/>
+                <meta key="musicName" value="Groundless"/>
+            </metadata></head>
+            <body><div song-part="verse">
+                <p begin="00:00:00.000" end="00:00:01.000" agent="v1">
+                    <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+                </p>
+            </div></body>
+        </tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.metadata.len(), 1);
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].agent.as_deref(), Some("v1"));
+        assert_eq!(data.lines[0].song_part.div.as_deref(), Some("verse"));
+    }
+
+    #[test]
+    fn a_leading_xml_declaration_and_doctype_are_ignored() {
+        let ttml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!DOCTYPE tt [<!ENTITY amp "&#38;">]>
+        <tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+
+    fn ttml_with_a_malformed_leading_div() -> &'static str {
+        r#"<tt><body>
+            <div begin="not-a-time">
+                <p begin="00:00:00.000" end="00:00:01.000">
+                    <span begin="00:00:00.000" end="00:00:01.000">broken</span>
+                </p>
+            </div>
+            <div>
+                <p begin="00:00:01.000" end="00:00:02.000">
+                    <span begin="00:00:01.000" end="00:00:02.000">ok</span>
+                </p>
+            </div>
+        </body></tt>"#
+    }
+
+    #[test]
+    fn without_recovery_a_malformed_div_fails_the_whole_parse() {
+        assert!(parse_ttml(ttml_with_a_malformed_leading_div()).is_err());
+    }
+
+    #[test]
+    fn recovery_resyncs_at_the_next_p_after_a_malformed_div() {
+        let options = TtmlParsingOptions {
+            recover_from_errors: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml_with_a_malformed_leading_div(), &options).unwrap();
+        let texts: Vec<&str> = data
+            .lines
+            .iter()
+            .map(|line| line.words[0].text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["broken", "ok"]);
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("skipping ahead to the next <p")));
+    }
+
+    #[test]
+    fn amll_meta_entries_are_collected_as_metadata() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:meta key="musicName" value="Groundless"/>
+            <amll:meta key="isrc" value="TCAFT2000101"/>
+        </metadata></head><body><div/></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(
+            data.metadata,
+            vec![
+                ("musicName".to_string(), "Groundless".to_string()),
+                ("isrc".to_string(), "TCAFT2000101".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn agent_definition_accepts_agent_attribute_alias() {
+        let ttml = r#"<tt><head><metadata>
+            <ttm:agent agent="v1" type="person"/>
+        </metadata></head><body><div/></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.agents[0].id, "v1");
+    }
+
+    #[test]
+    fn late_metadata_block_still_resolves_agents_referenced_earlier() {
+        // Some exporters emit a second `<metadata>` after `<body>`/`<div>`
+        // instead of collecting everything up front. Agent/meta handling
+        // isn't scoped to being "inside head", so it's naturally additive
+        // regardless of where in the document it shows up.
+        let ttml = r#"<tt><body>
+            <div>
+                <p begin="00:00:00.000" end="00:00:01.000" ttm:agent="v1">
+                    <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+                </p>
+            </div>
+            <metadata>
+                <ttm:agent xml:id="v1" type="person"/>
+            </metadata>
+        </body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.agents[0].id, "v1");
+        assert_eq!(data.agents[0].agent_type, AgentType::Person);
+    }
+
+    #[test]
+    fn two_metadata_blocks_merge_agents_and_meta_instead_of_duplicating() {
+        let ttml = r#"<tt><head>
+            <metadata>
+                <ttm:agent xml:id="v1" type="person"/>
+                <amll:meta key="musicName" value="Old Title"/>
+            </metadata>
+            <metadata>
+                <ttm:agent xml:id="v1" type="group"/>
+                <ttm:agent xml:id="v2" type="person"/>
+                <amll:meta key="musicName" value="New Title"/>
+                <amll:meta key="ncmMusicId" value="123"/>
+            </metadata>
+        </head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 2);
+        assert_eq!(
+            data.agents
+                .iter()
+                .find(|a| a.id == "v1")
+                .unwrap()
+                .agent_type,
+            AgentType::Group
+        );
+        assert_eq!(
+            data.metadata
+                .iter()
+                .filter(|(k, _)| k == "musicName")
+                .count(),
+            1
+        );
+        assert_eq!(
+            data.metadata
+                .iter()
+                .find(|(k, _)| k == "musicName")
+                .map(|(_, v)| v.as_str()),
+            Some("New Title")
+        );
+    }
+
+    #[test]
+    fn an_agent_redeclared_with_a_different_type_warns_but_keeps_the_later_type() {
+        let ttml = r#"<tt><head>
+            <metadata>
+                <ttm:agent xml:id="v1" type="person"/>
+            </metadata>
+            <metadata>
+                <ttm:agent xml:id="v1" type="group"/>
+            </metadata>
+        </head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.agents[0].agent_type, AgentType::Group);
+        assert!(data.warnings.iter().any(|w| w.contains("v1")));
+    }
+
+    #[test]
+    fn well_formed_isrc_does_not_warn() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:meta key="isrc" value="TCAFT2000101"/>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(!data.warnings.iter().any(|w| w.contains("isrc")));
+    }
+
+    #[test]
+    fn malformed_isrc_warns_but_still_parses() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:meta key="isrc" value="not-an-isrc"/>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.iter().any(|w| w.contains("isrc")));
+        assert_eq!(data.lines.len(), 1);
+    }
+
+    #[test]
+    fn capitalized_itunes_timing_line_is_recognized() {
+        let ttml = r#"<tt itunes:timing="Line"><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.is_line_timing_mode, Some(true));
+    }
+
+    #[test]
+    fn lowercase_itunes_timing_word_is_still_recognized() {
+        let ttml = r#"<tt itunes:timing="word"><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.is_line_timing_mode, Some(false));
+    }
+
+    #[test]
+    fn missing_itunes_timing_leaves_it_unset() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.is_line_timing_mode, None);
+    }
+
+    #[test]
+    fn validate_ttml_quick_reports_the_same_warnings_without_building_lines() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:32.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+            </p>
+        </div></body></tt>"#;
+        let issues = validate_ttml_quick(ttml, &TtmlParsingOptions::default()).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("lingers"));
+    }
+
+    #[test]
+    fn validate_ttml_quick_is_clean_for_a_well_formed_document() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let issues = validate_ttml_quick(ttml, &TtmlParsingOptions::default()).unwrap();
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn inline_chorus_agent_reference_is_synthesized_as_group_type() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" ttm:agent="合唱">
+                <span begin="00:00:00.000" end="00:00:01.000">la</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.agents.len(), 1);
+        assert_eq!(data.agents[0].id, "合唱");
+        assert_eq!(data.agents[0].agent_type, AgentType::Group);
+    }
+
+    #[test]
+    fn div_and_p_song_part_are_captured_separately() {
+        let ttml = r#"<tt><body>
+            <div itunes:song-part="verse">
+                <p begin="00:00:00.000" end="00:00:01.000">
+                    <span begin="00:00:00.000" end="00:00:01.000">a</span>
+                </p>
+                <p begin="00:00:01.000" end="00:00:02.000" itunes:song-part="chorus">
+                    <span begin="00:00:01.000" end="00:00:02.000">b</span>
+                </p>
+            </div>
+        </body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].song_part.div.as_deref(), Some("verse"));
+        assert_eq!(data.lines[0].song_part.p, None);
+        assert_eq!(data.lines[0].song_part.effective(), Some("verse"));
+
+        assert_eq!(data.lines[1].song_part.div.as_deref(), Some("verse"));
+        assert_eq!(data.lines[1].song_part.p.as_deref(), Some("chorus"));
+        assert_eq!(data.lines[1].song_part.effective(), Some("chorus"));
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_hours_and_minutes_forms() {
+        let options = TtmlParsingOptions::default();
+        assert_eq!(parse_ttml_time("00:00:01.234", &options).unwrap(), 1234);
+        assert_eq!(parse_ttml_time("01:01.001", &options).unwrap(), 61001);
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_frame_offsets_at_default_rate() {
+        let options = TtmlParsingOptions::default();
+        // Default frame rate is 30fps, so 30 frames is exactly one second.
+        assert_eq!(parse_ttml_time("30f", &options).unwrap(), 1000);
+        assert_eq!(parse_ttml_time("15f", &options).unwrap(), 500);
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_fractional_frame_offsets() {
+        let options = TtmlParsingOptions {
+            frame_rate: 25.0,
+            ..TtmlParsingOptions::default()
+        };
+        assert_eq!(parse_ttml_time("100.5f", &options).unwrap(), 4020);
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_tick_offsets_at_default_rate() {
+        let options = TtmlParsingOptions::default();
+        // Default tick rate is 1000 ticks/second, so ticks equal milliseconds.
+        assert_eq!(parse_ttml_time("12t", &options).unwrap(), 12);
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_tick_offsets_at_custom_rate() {
+        let options = TtmlParsingOptions {
+            tick_rate: 10_000_000.0,
+            ..TtmlParsingOptions::default()
+        };
+        assert!(parse_ttml_time("5_000_000t", &options).is_err());
+        assert_eq!(parse_ttml_time("5000000t", &options).unwrap(), 500);
+    }
+
+    #[test]
+    fn parse_ttml_time_supports_plain_seconds_and_milliseconds() {
+        let options = TtmlParsingOptions::default();
+        assert_eq!(parse_ttml_time("1.5s", &options).unwrap(), 1500);
+        assert_eq!(parse_ttml_time("250ms", &options).unwrap(), 250);
+    }
+
+    #[test]
+    fn parse_ttml_time_normalizes_fullwidth_clock_time() {
+        let options = TtmlParsingOptions::default();
+        assert_eq!(
+            parse_ttml_time("０３：０８．００２", &options).unwrap(),
+            parse_ttml_time("03:08.002", &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn an_overlong_leading_zero_hour_segment_parses_normally() {
+        let options = TtmlParsingOptions::default();
+        assert_eq!(
+            parse_ttml_time("000:00:05.000", &options).unwrap(),
+            parse_ttml_time("00:00:05.000", &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_four_digit_fraction_with_a_trailing_zero_is_losslessly_truncated() {
+        let options = TtmlParsingOptions::default();
+        assert_eq!(
+            parse_ttml_time("00:00:05.0500", &options).unwrap(),
+            parse_ttml_time("00:00:05.050", &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_four_digit_fraction_with_a_nonzero_trailing_digit_is_rejected() {
+        let options = TtmlParsingOptions::default();
+        assert!(parse_ttml_time("00:00:05.0505", &options).is_err());
+    }
+
+    #[test]
+    fn fullwidth_timestamp_on_a_span_is_normalized_and_warns() {
+        let ttml = "<tt><body><div>\
+            <p begin=\"00:00:00.000\" end=\"00:00:03.000\">\
+                <span begin=\"００：００．０００\" end=\"００：０３．０００\">hi</span>\
+            </p>\
+        </div></body></tt>";
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].start_ms, 0);
+        assert_eq!(data.lines[0].words[0].end_ms, 3000);
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("full-width digits or separators")));
+    }
+
+    #[test]
+    fn span_with_only_rt_text_becomes_a_line_romanization() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000"><rt>konnichiwa</rt></span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let line = &data.lines[0];
+        assert!(line.words.is_empty());
+        assert_eq!(line.romanizations.len(), 1);
+        assert_eq!(line.romanizations[0].text, "konnichiwa");
+        assert_eq!(line.romanizations[0].lang, None);
+    }
+
+    #[test]
+    fn span_with_base_text_and_rt_keeps_the_base_as_a_word() {
+        // A span with both base text and an `<rt>` isn't the degenerate
+        // "whole-line reading" case, so it's still just a plain word; the
+        // `<rt>` content is ignored rather than guessed at.
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">日<rt>ひ</rt></span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let line = &data.lines[0];
+        assert_eq!(line.words.len(), 1);
+        assert_eq!(line.words[0].text, "日");
+        assert!(line.romanizations.is_empty());
+    }
+
+    #[test]
+    fn preserve_unknown_metadata_collects_custom_namespaced_elements() {
+        let ttml = r#"<tt><head><metadata>
+            <myns:bpm>120</myns:bpm>
+        </metadata></head><body><div></div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            preserve_unknown_metadata: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(
+            data.raw_metadata,
+            vec![("myns:bpm".to_string(), "120".to_string())]
+        );
+    }
+
+    #[test]
+    fn unknown_metadata_is_dropped_when_the_option_is_off() {
+        let ttml = r#"<tt><head><metadata>
+            <myns:bpm>120</myns:bpm>
+        </metadata></head><body><div></div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.raw_metadata.is_empty());
+    }
+
+    #[test]
+    fn unknown_elements_outside_metadata_are_never_collected() {
+        let ttml = r#"<tt><body><div>
+            <myns:bpm>120</myns:bpm>
+            <p begin="00:00:00.000" end="00:00:01.000"></p>
+        </div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            preserve_unknown_metadata: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert!(data.raw_metadata.is_empty());
+    }
+
+    #[test]
+    fn inline_x_roman_span_captures_text_and_scheme() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">日</span>
+                <span ttm:role="x-roman" xml:scheme="hepburn">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let line = &data.lines[0];
+        assert_eq!(line.words.len(), 1);
+        assert_eq!(line.romanizations.len(), 1);
+        assert_eq!(line.romanizations[0].text, "hi");
+        assert_eq!(line.romanizations[0].scheme.as_deref(), Some("hepburn"));
+    }
+
+    #[test]
+    fn romanization_scheme_round_trips_through_generate_and_parse() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">日</span>
+                <span ttm:role="x-roman" xml:scheme="hepburn">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let regenerated = crate::generate_ttml(&data).unwrap();
+        assert!(regenerated.contains("xml:scheme=\"hepburn\""));
+        let roundtripped = parse_ttml(&regenerated).unwrap();
+        assert_eq!(
+            roundtripped.lines[0].romanizations[0].scheme.as_deref(),
+            Some("hepburn")
+        );
+    }
+
+    #[test]
+    fn inline_x_bg_span_captures_text_and_timing_separately_from_words() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                <span ttm:role="x-bg" begin="00:00:00.000" end="00:00:02.000">oooh</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let line = &data.lines[0];
+        assert_eq!(line.words.len(), 1);
+        assert_eq!(line.background.len(), 1);
+        assert_eq!(line.background[0].text, "oooh");
+        assert_eq!(line.background[0].start_ms, 0);
+        assert_eq!(line.background[0].end_ms, 2000);
+    }
+
+    #[test]
+    fn x_bg_parentheses_are_stripped_by_default() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span ttm:role="x-bg" begin="00:00:00.000" end="00:00:02.000">(oooh)</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].background[0].text, "oooh");
+    }
+
+    #[test]
+    fn x_bg_parentheses_are_kept_when_the_option_is_off() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span ttm:role="x-bg" begin="00:00:00.000" end="00:00:02.000">(oooh)</span>
+            </p>
+        </div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            strip_background_parentheses: false,
+            ..Default::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(data.lines[0].background[0].text, "(oooh)");
+    }
+
+    #[test]
+    fn mismatched_width_x_bg_parentheses_warn_but_are_left_unstripped() {
+        let ttml = "<tt><body><div>\
+            <p begin=\"00:00:00.000\" end=\"00:00:02.000\">\
+                <span ttm:role=\"x-bg\" begin=\"00:00:00.000\" end=\"00:00:02.000\">(oooh）</span>\
+            </p>\
+        </div></body></tt>";
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].background[0].text, "(oooh）");
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("mismatched half/full-width parentheses")));
+    }
+
+    #[test]
+    fn a_percentage_span_timestamp_warns_and_keeps_the_syllable_text() {
+        let ttml = "<tt><body><div>\
+            <p begin=\"00:00:00.000\" end=\"00:00:02.000\">\
+                <span begin=\"0%\" end=\"50%\">oops</span>\
+            </p>\
+        </div></body></tt>";
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "oops");
+        assert_eq!(data.lines[0].words[0].start_ms, 0);
+        assert_eq!(data.lines[0].words[0].end_ms, 2000);
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("unsupported percentage-based timing")));
+    }
+
+    #[test]
+    fn a_percentage_span_timestamp_does_not_disturb_its_normally_timed_siblings() {
+        let ttml = "<tt><body><div>\
+            <p begin=\"00:00:00.000\" end=\"00:00:02.000\">\
+                <span begin=\"00:00:00.000\" end=\"00:00:01.000\">hi</span>\
+                <span begin=\"50%\" end=\"100%\">there</span>\
+            </p>\
+        </div></body></tt>";
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words.len(), 2);
+        assert_eq!(data.lines[0].words[0].start_ms, 0);
+        assert_eq!(data.lines[0].words[0].end_ms, 1000);
+        assert_eq!(data.lines[0].words[1].text, "there");
+        assert_eq!(data.lines[0].words[1].start_ms, 0);
+        assert_eq!(data.lines[0].words[1].end_ms, 2000);
+    }
+
+    #[test]
+    fn inline_x_translation_span_is_collected_as_a_translation() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                <span ttm:role="x-translation" xml:lang="zh">你好</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations[0].text, "你好");
+        assert_eq!(data.lines[0].translations[0].lang.as_deref(), Some("zh"));
+    }
+
+    #[test]
+    fn trailing_bare_text_after_a_word_timed_ps_spans_is_ignored_by_default() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                你好
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.lines[0].translations.is_empty());
+    }
+
+    #[test]
+    fn trailing_bare_text_is_collected_as_a_translation_when_enabled() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                你好
+            </p>
+        </div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            trailing_text_as_translation: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(data.lines[0].translations.len(), 1);
+        assert_eq!(data.lines[0].translations[0].text, "你好");
+        assert!(data.lines[0].translations[0].lang.is_none());
+    }
+
+    #[test]
+    fn a_translation_present_both_inline_and_in_head_is_not_duplicated() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:translation for="L1" lang="zh">你好</amll:translation>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000" itunes:key="L1">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                <span ttm:role="x-translation" xml:lang="zh">你好</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations.len(), 1);
+    }
+
+    #[test]
+    fn word_timed_translation_syllables_are_assembled_from_nested_spans() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+                <span ttm:role="x-translation" xml:lang="zh">
+                    <span begin="00:00:00.000" end="00:00:01.000">你好</span>
+                    <span begin="00:00:01.000" end="00:00:02.000">世界</span>
+                </span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let translation = &data.lines[0].translations[0];
+        assert_eq!(translation.lang.as_deref(), Some("zh"));
+        assert_eq!(translation.text, "你好 世界");
+        assert_eq!(translation.syllables.len(), 2);
+        assert_eq!(translation.syllables[0].text, "你好");
+        assert_eq!(translation.syllables[0].start_ms, 0);
+        assert_eq!(translation.syllables[1].text, "世界");
+        assert_eq!(translation.syllables[1].end_ms, 2000);
+    }
+
+    #[test]
+    fn a_word_timed_translation_round_trips_through_generate_and_parse() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello</span>
+                <span begin="00:00:01.000" end="00:00:02.000">world</span>
+                <span ttm:role="x-translation" xml:lang="zh">
+                    <span begin="00:00:00.000" end="00:00:01.000">你好</span>
+                    <span begin="00:00:01.000" end="00:00:02.000">世界</span>
+                </span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let options = TtmlParsingOptions::default();
+        let generation_options = crate::TtmlGenerationOptions {
+            translation_layout: crate::TranslationLayout::Inline,
+            ..crate::TtmlGenerationOptions::default()
+        };
+        let regenerated = crate::generate_ttml_inner(&data, &generation_options).unwrap();
+        let roundtripped = parse_ttml_with_options(&regenerated, &options).unwrap();
+        let translation = &roundtripped.lines[0].translations[0];
+        assert_eq!(translation.syllables.len(), 2);
+        assert_eq!(translation.syllables[0].text, "你好");
+        assert_eq!(translation.syllables[1].text, "世界");
+    }
+
+    #[test]
+    fn background_track_round_trips_for_a_line_timed_line() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span begin="00:00:00.000" end="00:00:02.000">hello</span>
+                <span ttm:role="x-bg" begin="00:00:00.000" end="00:00:02.000">oooh</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let regenerated = crate::generate_ttml(&data).unwrap();
+        // Only one main-track word on the only line, so the document comes
+        // out line-timed; the background span must still be emitted.
+        assert!(regenerated.contains("itunes:timing=\"Line\""));
+        assert!(regenerated.contains("ttm:role=\"x-bg\""));
+        let roundtripped = parse_ttml(&regenerated).unwrap();
+        assert_eq!(roundtripped.lines[0].background[0].text, "oooh");
+    }
+
+    #[test]
+    fn span_xml_lang_is_captured_on_the_word() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000" xml:lang="ja">日</span>
+                <span begin="00:00:01.000" end="00:00:02.000" xml:lang="en">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].lang.as_deref(), Some("ja"));
+        assert_eq!(data.lines[0].words[1].lang.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn word_without_xml_lang_leaves_it_unset() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">日</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].lang, None);
+    }
+
+    #[test]
+    fn syllable_lang_round_trips_through_generate_and_parse() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000" xml:lang="en">hi</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        let regenerated = crate::generate_ttml(&data).unwrap();
+        assert!(regenerated.contains("xml:lang=\"en\""));
+        let roundtripped = parse_ttml(&regenerated).unwrap();
+        assert_eq!(roundtripped.lines[0].words[0].lang.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn stray_bracket_timestamp_in_word_text_warns_but_is_kept_by_default() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">[00:01.00]hello</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "[00:01.00]hello");
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("leftover timestamp")));
+    }
+
+    #[test]
+    fn stray_angle_timestamp_is_stripped_when_the_option_is_on() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">&lt;00:01.00&gt;hello</span>
+            </p>
+        </div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            strip_stray_timestamps: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hello");
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("leftover timestamp")));
+    }
+
+    #[test]
+    fn ordinary_word_text_without_a_timestamp_shape_does_not_warn() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">
+                <span begin="00:00:00.000" end="00:00:01.000">hello [world]</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hello [world]");
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn xml_space_preserve_keeps_double_spaces_while_a_normal_span_collapses_them() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:02.000">
+                <span xml:space="preserve" begin="00:00:00.000" end="00:00:01.000">a  b</span>
+                <span begin="00:00:01.000" end="00:00:02.000">c  d</span>
+            </p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "a  b");
+        assert_eq!(data.lines[0].words[1].text, "c d");
+    }
+
+    #[test]
+    fn comma_separated_translation_for_applies_to_every_listed_line() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:translation for="L1, L2" lang="zh">合并的翻译</amll:translation>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:key="L2">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations[0].text, "合并的翻译");
+        assert_eq!(data.lines[1].translations[0].text, "合并的翻译");
+        assert_eq!(data.lines[0].translations[0].lang.as_deref(), Some("zh"));
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn br_with_surrounding_spaces_merges_into_a_single_space() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:translation for="L1" lang="zh">文字 <br/> 文字</amll:translation>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations[0].text, "文字 文字");
+    }
+
+    #[test]
+    fn br_with_no_surrounding_spaces_still_inserts_one() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:translation for="L1" lang="zh">文字<br/>文字</amll:translation>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations[0].text, "文字 文字");
+    }
+
+    #[test]
+    fn translation_referencing_a_missing_key_warns_but_still_applies_the_rest() {
+        let ttml = r#"<tt><head><metadata>
+            <amll:translation for="L1,L9" lang="zh">部分翻译</amll:translation>
+        </metadata></head><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].translations[0].text, "部分翻译");
+        assert!(data.warnings.iter().any(|w| w.contains("L9")));
+    }
+
+    #[test]
+    fn line_starting_before_its_divs_declared_begin_warns() {
+        let ttml = r#"<tt><body><div begin="00:00:05.000" end="00:00:10.000">
+            <p begin="00:00:01.000" end="00:00:06.000">early</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("starts before its div's declared begin")));
+    }
+
+    #[test]
+    fn line_within_its_divs_declared_bounds_does_not_warn() {
+        let ttml = r#"<tt><body><div begin="00:00:00.000" end="00:00:10.000">
+            <p begin="00:00:01.000" end="00:00:06.000">on time</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn a_key_far_beyond_the_line_count_warns_about_missing_or_skipped_keys() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:key="L200">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("highest itunes:key is L200") && w.contains("2 lines")));
+    }
+
+    #[test]
+    fn sequential_keys_matching_the_line_count_do_not_warn() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:key="L2">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn lines_without_any_itunes_key_do_not_trigger_the_diagnostic() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn all_lines_ending_under_half_a_second_warns_about_timestamp_magnitude() {
+        let mut ttml = "<tt><body><div>".to_string();
+        for i in 0..12u64 {
+            ttml.push_str(&format!(
+                "<p begin=\"00:00:00.0{i:02}\" end=\"00:00:00.0{:02}\">line {i}</p>",
+                i + 1
+            ));
+        }
+        ttml.push_str("</div></body></tt>");
+        let data = parse_ttml(&ttml).unwrap();
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("timestamps may have been written in the wrong unit")));
+    }
+
+    #[test]
+    fn a_short_document_with_few_lines_does_not_trigger_the_magnitude_check() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.001" end="00:00:00.002">a</p>
+            <p begin="00:00:00.002" end="00:00:00.003">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn plausibly_scaled_timestamps_across_many_lines_do_not_warn() {
+        let mut ttml = "<tt><body><div>".to_string();
+        for i in 0..12u64 {
+            ttml.push_str(&format!(
+                "<p begin=\"00:00:{i:02}.000\" end=\"00:00:{:02}.000\">line {i}</p>",
+                i + 1
+            ));
+        }
+        ttml.push_str("</div></body></tt>");
+        let data = parse_ttml(&ttml).unwrap();
+        assert!(!data
+            .warnings
+            .iter()
+            .any(|w| w.contains("timestamps may have been written in the wrong unit")));
+    }
+
+    #[test]
+    fn a_mostly_untimed_file_is_switched_to_line_mode_when_the_ratio_option_is_set() {
+        let mut ttml =
+            "<tt itunes:timing=\"Word\"><body><div><p begin=\"00:00:00.000\" end=\"00:00:10.000\">"
+                .to_string();
+        ttml.push_str("<span begin=\"00:00:00.000\" end=\"00:00:01.000\">hi</span>");
+        for i in 0..9u64 {
+            ttml.push_str(&format!("<span>word{i}</span>"));
+        }
+        ttml.push_str("</p></div></body></tt>");
+        let options = TtmlParsingOptions {
+            line_mode_timed_ratio: 0.5,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(&ttml, &options).unwrap();
+        assert_eq!(data.is_line_timing_mode, Some(true));
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("treating the document as line-timed")));
+    }
+
+    #[test]
+    fn the_default_ratio_never_overrides_a_mostly_untimed_file() {
+        let mut ttml =
+            "<tt itunes:timing=\"Word\"><body><div><p begin=\"00:00:00.000\" end=\"00:00:10.000\">"
+                .to_string();
+        ttml.push_str("<span begin=\"00:00:00.000\" end=\"00:00:01.000\">hi</span>");
+        for i in 0..9u64 {
+            ttml.push_str(&format!("<span>word{i}</span>"));
+        }
+        ttml.push_str("</p></div></body></tt>");
+        let data = parse_ttml(&ttml).unwrap();
+        assert_eq!(data.is_line_timing_mode, Some(false));
+        assert!(!data
+            .warnings
+            .iter()
+            .any(|w| w.contains("treating the document as line-timed")));
+    }
+
+    #[test]
+    fn a_line_without_an_agent_inherits_the_previous_lines_agent_when_enabled() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" ttm:agent="v1">first</p>
+            <p begin="00:00:01.000" end="00:00:02.000">second</p>
+        </div></body></tt>"#;
+        let options = TtmlParsingOptions {
+            inherit_agent_within_div: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(data.lines[0].agent.as_deref(), Some("v1"));
+        assert_eq!(data.lines[1].agent.as_deref(), Some("v1"));
+    }
+
+    #[test]
+    fn a_line_without_an_agent_stays_unset_when_inheritance_is_disabled() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" ttm:agent="v1">first</p>
+            <p begin="00:00:01.000" end="00:00:02.000">second</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert_eq!(data.lines[0].agent.as_deref(), Some("v1"));
+        assert_eq!(data.lines[1].agent, None);
+    }
+
+    #[test]
+    fn agent_inheritance_does_not_cross_a_div_boundary() {
+        let ttml = r#"<tt><body>
+            <div>
+                <p begin="00:00:00.000" end="00:00:01.000" ttm:agent="v1">first</p>
+            </div>
+            <div>
+                <p begin="00:00:01.000" end="00:00:02.000">second</p>
+            </div>
+        </body></tt>"#;
+        let options = TtmlParsingOptions {
+            inherit_agent_within_div: true,
+            ..TtmlParsingOptions::default()
+        };
+        let data = parse_ttml_with_options(ttml, &options).unwrap();
+        assert_eq!(data.lines[0].agent.as_deref(), Some("v1"));
+        assert_eq!(data.lines[1].agent, None);
+    }
+
+    #[test]
+    fn a_key_used_by_two_lines_warns_about_the_duplicate() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:key="L1">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(data
+            .warnings
+            .iter()
+            .any(|w| w.contains("itunes:key `L1` is used by more than one line")));
+    }
+
+    #[test]
+    fn distinct_keys_do_not_warn_about_duplicates() {
+        let ttml = r#"<tt><body><div>
+            <p begin="00:00:00.000" end="00:00:01.000" itunes:key="L1">a</p>
+            <p begin="00:00:01.000" end="00:00:02.000" itunes:key="L2">b</p>
+        </div></body></tt>"#;
+        let data = parse_ttml(ttml).unwrap();
+        assert!(!data
+            .warnings
+            .iter()
+            .any(|w| w.contains("is used by more than one line")));
+    }
+
+    #[test]
+    fn parse_ttml_bytes_parses_plain_utf8_input_directly() {
+        let data = parse_ttml_bytes(SAMPLE_TTML_FOR_BYTES.as_bytes()).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+
+    #[test]
+    fn parse_ttml_bytes_rejects_invalid_utf8() {
+        let err = parse_ttml_bytes(&[0xff, 0xfe, 0xfd]).unwrap_err();
+        assert!(err.to_string().contains("not valid UTF-8"));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn parse_ttml_bytes_decompresses_gzip_input() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(SAMPLE_TTML_FOR_BYTES.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let data = parse_ttml_bytes(&compressed).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hi");
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn parse_ttml_bytes_reports_gzip_input_as_unsupported_without_the_feature() {
+        let gzip_looking_bytes = [0x1f, 0x8b, 0x00];
+        let err = parse_ttml_bytes(&gzip_looking_bytes).unwrap_err();
+        assert!(err.to_string().contains("gzip"));
+    }
+
+    const SAMPLE_TTML_FOR_BYTES: &str = r#"<tt><body><div>
+        <p begin="00:00:00.000" end="00:00:01.000">
+            <span begin="00:00:00.000" end="00:00:01.000">hi</span>
+        </p>
+    </div></body></tt>"#;
+}
@@ -0,0 +1,170 @@
+//! Parsing of QQ Music's QRC format: `[offset:N]` followed by one
+//! `[start,duration]word(word_offset,word_duration)...` line per lyric line,
+//! with word offsets/durations relative to the start of the recording.
+//!
+//! This crate only reads QRC back into [`ParsedSourceData`] -- generating it
+//! lives with the other on-disk export formats in `rebuild-folder`, since
+//! nothing else in this crate needs to produce it.
+
+use crate::errors::ConvertError;
+use crate::types::{LyricLine, ParsedSourceData, Word};
+
+/// Parse a QRC file into [`ParsedSourceData`]. A leading `[offset:N]` line
+/// is kept as a `"offset"` [`ParsedSourceData::metadata`] entry rather than
+/// being applied to the timestamps, matching how the TTML parser leaves its
+/// own `offset` metadata for the caller to apply.
+pub fn parse_qrc(content: &str) -> Result<ParsedSourceData, ConvertError> {
+    let mut lines = Vec::new();
+    let mut metadata = Vec::new();
+
+    for (line_no, raw_line) in content.lines().enumerate() {
+        let raw_line = raw_line.trim();
+        if raw_line.is_empty() {
+            continue;
+        }
+        if let Some(value) = raw_line
+            .strip_prefix("[offset:")
+            .and_then(|s| s.strip_suffix(']'))
+        {
+            metadata.push(("offset".to_string(), value.trim().to_string()));
+            continue;
+        }
+
+        let (line_start, line_duration, words) = parse_qrc_line(raw_line, line_no + 1)?;
+        let mut line = LyricLine::new(line_start, line_start + line_duration);
+        line.words = words;
+        lines.push(line);
+    }
+
+    Ok(ParsedSourceData {
+        lines,
+        metadata,
+        ..Default::default()
+    })
+}
+
+/// Parse one `[start,duration]word(offset,duration)...` line into the
+/// line's own timing and its syllables.
+fn parse_qrc_line(raw: &str, line_no: usize) -> Result<(u64, u64, Vec<Word>), ConvertError> {
+    if !raw.starts_with('[') {
+        return Err(ConvertError::Malformed(format!(
+            "qrc line {line_no} doesn't start with a '[start,duration]' header"
+        )));
+    }
+    let close = raw.find(']').ok_or_else(|| {
+        ConvertError::Malformed(format!("qrc line {line_no} is missing a closing ']'"))
+    })?;
+    let header = &raw[1..close];
+    let (start_str, duration_str) = header.split_once(',').ok_or_else(|| {
+        ConvertError::Malformed(format!(
+            "qrc line {line_no} header `{header}` is missing a comma"
+        ))
+    })?;
+    let line_start: u64 = start_str.trim().parse().map_err(|_| {
+        ConvertError::Malformed(format!(
+            "qrc line {line_no} has a non-numeric start `{start_str}`"
+        ))
+    })?;
+    let line_duration: u64 = duration_str.trim().parse().map_err(|_| {
+        ConvertError::Malformed(format!(
+            "qrc line {line_no} has a non-numeric duration `{duration_str}`"
+        ))
+    })?;
+
+    let mut words = Vec::new();
+    let mut rest = &raw[close + 1..];
+    while !rest.is_empty() {
+        let open = rest.find('(').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "qrc line {line_no} has a syllable with no '(offset,duration)'"
+            ))
+        })?;
+        let text = &rest[..open];
+        let close_paren = rest[open..].find(')').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "qrc line {line_no} has an unterminated '(offset,duration)'"
+            ))
+        })? + open;
+        let timing = &rest[open + 1..close_paren];
+        let (offset_str, word_duration_str) = timing.split_once(',').ok_or_else(|| {
+            ConvertError::Malformed(format!(
+                "qrc line {line_no} syllable timing `{timing}` is missing a comma"
+            ))
+        })?;
+        let word_start: u64 = offset_str.trim().parse().map_err(|_| {
+            ConvertError::Malformed(format!(
+                "qrc line {line_no} has a non-numeric syllable offset `{offset_str}`"
+            ))
+        })?;
+        let word_duration: u64 = word_duration_str.trim().parse().map_err(|_| {
+            ConvertError::Malformed(format!(
+                "qrc line {line_no} has a non-numeric syllable duration `{word_duration_str}`"
+            ))
+        })?;
+        words.push(Word {
+            start_ms: word_start,
+            end_ms: word_start + word_duration,
+            text: text.to_string(),
+            lang: None,
+            furigana: None,
+        });
+        rest = &rest[close_paren + 1..];
+    }
+    Ok((line_start, line_duration, words))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, start_ms: u64, end_ms: u64) -> Word {
+        Word {
+            start_ms,
+            end_ms,
+            text: text.into(),
+            lang: None,
+            furigana: None,
+        }
+    }
+
+    #[test]
+    fn parse_qrc_reads_a_line_with_absolute_word_offsets() {
+        let data = parse_qrc("[offset:0]\n[1000,2000]hello(1000,1000)world(2000,1000)\n").unwrap();
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].start_ms, 1000);
+        assert_eq!(data.lines[0].end_ms, 3000);
+        assert_eq!(
+            data.lines[0].words,
+            vec![word("hello", 1000, 2000), word("world", 2000, 3000)]
+        );
+    }
+
+    #[test]
+    fn parse_qrc_keeps_the_offset_header_as_metadata() {
+        let data = parse_qrc("[offset:500]\n[0,1000]hi(0,1000)\n").unwrap();
+        assert_eq!(
+            data.metadata,
+            vec![("offset".to_string(), "500".to_string())]
+        );
+    }
+
+    #[test]
+    fn parse_qrc_rejects_a_line_missing_its_header_brackets() {
+        assert!(parse_qrc("hi(0,1000)\n").is_err());
+    }
+
+    #[test]
+    fn parse_qrc_rejects_a_syllable_with_no_timing() {
+        assert!(parse_qrc("[0,1000]hi\n").is_err());
+    }
+
+    #[test]
+    fn a_qrc_line_in_the_rebuild_folder_writer_shape_keeps_words() {
+        // Mirrors the `[start,duration]word(offset,duration)...` shape
+        // written by rebuild-folder's `to_qrc`, without a dependency on it.
+        let qrc = "[offset:0]\n[1000,2000]hello(1000,1000)world(2000,1000)\n";
+        let data = parse_qrc(qrc).unwrap();
+        assert_eq!(data.lines[0].words[0].text, "hello");
+        assert_eq!(data.lines[0].words[1].text, "world");
+    }
+}
@@ -0,0 +1,170 @@
+//! Generation of Musixmatch-style "richsync" rich-sync JSON.
+
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::errors::ConvertError;
+use crate::types::LyricLine;
+
+/// One line's worth of richsync timing: `ts`/`te` are the line's begin/end
+/// in seconds, and `l` is every character with its own offset (in seconds,
+/// relative to `ts`) derived from the syllable it belongs to.
+#[derive(Debug, Serialize)]
+struct RichsyncLine {
+    ts: f64,
+    te: f64,
+    l: Vec<RichsyncChar>,
+}
+
+#[derive(Debug, Serialize)]
+struct RichsyncChar {
+    c: String,
+    o: f64,
+}
+
+/// Convert word-timed main tracks into Musixmatch's richsync JSON shape: an
+/// array of `{ts, te, l: [{c, o}]}` per line.
+///
+/// Each word's characters are spread evenly across that word's own
+/// `start_ms..end_ms`, since this crate only carries syllable-level timing
+/// rather than true per-character timing; a single space character is
+/// inserted between consecutive words, offset at the boundary between them.
+///
+/// "Character" here means an extended grapheme cluster, not a Rust `char`:
+/// an emoji built from several code points joined by zero-width joiners
+/// (e.g. a family emoji) is one user-visible glyph and gets one timing
+/// slot, rather than being split into several code points that would each
+/// claim their own (visually meaningless) slice of the word's duration.
+///
+/// `lines` with no word-level timing (every line has at most one word) are
+/// considered line-timed rather than word-timed, and richsync has nothing
+/// meaningful to say about per-character position within them, so an empty
+/// array is emitted instead.
+pub fn generate_richsync(lines: &[LyricLine]) -> Result<String, ConvertError> {
+    let has_word_timing = lines.iter().any(|line| line.words.len() > 1);
+    if !has_word_timing {
+        return Ok("[]".to_string());
+    }
+
+    let richsync_lines: Vec<RichsyncLine> = lines
+        .iter()
+        .map(|line| RichsyncLine {
+            ts: line.start_ms as f64 / 1000.0,
+            te: line.end_ms as f64 / 1000.0,
+            l: richsync_chars(line),
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&richsync_lines)?)
+}
+
+fn richsync_chars(line: &LyricLine) -> Vec<RichsyncChar> {
+    let mut chars = Vec::new();
+    for (i, word) in line.words.iter().enumerate() {
+        if i > 0 {
+            chars.push(RichsyncChar {
+                c: " ".to_string(),
+                o: word.start_ms.saturating_sub(line.start_ms) as f64 / 1000.0,
+            });
+        }
+        let graphemes: Vec<&str> = word.text.graphemes(true).collect();
+        let count = graphemes.len() as u64;
+        let duration = word.end_ms.saturating_sub(word.start_ms);
+        for (j, grapheme) in graphemes.into_iter().enumerate() {
+            let j = j as u64;
+            let char_start_ms = word.start_ms + duration * j / count.max(1);
+            chars.push(RichsyncChar {
+                c: grapheme.to_string(),
+                o: char_start_ms.saturating_sub(line.start_ms) as f64 / 1000.0,
+            });
+        }
+    }
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Word;
+
+    fn word_timed_lines() -> Vec<LyricLine> {
+        let mut line = LyricLine::new(0, 2000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "hi".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 1000,
+            end_ms: 2000,
+            text: "yo".into(),
+            lang: None,
+            furigana: None,
+        });
+        vec![line]
+    }
+
+    #[test]
+    fn line_timed_source_emits_an_empty_array() {
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "whole line".into(),
+            lang: None,
+            furigana: None,
+        });
+        assert_eq!(generate_richsync(&[line]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn word_timed_source_round_trips_through_the_richsync_schema() {
+        let json = generate_richsync(&word_timed_lines()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let lines = value.as_array().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["ts"], 0.0);
+        assert_eq!(lines[0]["te"], 2.0);
+        let chars = lines[0]["l"].as_array().unwrap();
+        // "hi" (2 chars) + one space + "yo" (2 chars) = 5 entries.
+        assert_eq!(chars.len(), 5);
+        assert_eq!(chars[0]["c"], "h");
+        assert_eq!(chars[0]["o"], 0.0);
+        assert_eq!(chars[2]["c"], " ");
+        assert_eq!(chars[2]["o"], 1.0);
+        assert_eq!(chars[3]["c"], "y");
+        assert_eq!(chars[3]["o"], 1.0);
+    }
+
+    #[test]
+    fn a_zwj_emoji_sequence_is_kept_as_a_single_grapheme() {
+        // The "family: man, woman, girl" emoji is four code points joined
+        // by U+200D (ZWJ); splitting on `char` would scatter it across
+        // several nonsensical timing slots instead of the one it deserves.
+        let family_emoji = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: family_emoji.to_string(),
+            lang: None,
+            furigana: None,
+        });
+        line.words.push(Word {
+            start_ms: 500,
+            end_ms: 1000,
+            text: "!".to_string(),
+            lang: None,
+            furigana: None,
+        });
+        let json = generate_richsync(&[line]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let chars = value[0]["l"].as_array().unwrap();
+        // The emoji (1 grapheme) + one space + "!" (1 grapheme) = 3 entries.
+        assert_eq!(chars.len(), 3);
+        assert_eq!(chars[0]["c"], family_emoji);
+        assert_eq!(chars[0]["o"], 0.0);
+    }
+}
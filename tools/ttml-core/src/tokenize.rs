@@ -0,0 +1,195 @@
+//! Automatic tokenization of plain, unsplit lyric text into syllable-sized
+//! pieces, for callers that need to pre-split text before it has any timing
+//! at all (e.g. an editor offering per-syllable timing on paste).
+//!
+//! CJK ideographs and kana are split one extended grapheme cluster at a
+//! time (so a ZWJ-joined sequence stays a single token), runs of ASCII
+//! letters or ASCII digits are kept together as whole words, and anything
+//! else (punctuation, symbols, non-ASCII letters such as `é`) becomes its
+//! own token. Whitespace separates
+//! tokens without becoming one itself. A small built-in hyphenation
+//! dictionary further splits recognized English words at their natural
+//! syllable boundaries; words it doesn't recognize are kept whole rather
+//! than guessed at, since a wrong split is worse than none.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The broad category a single character falls into, as used by
+/// [`auto_tokenize`] to decide where token boundaries fall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharType {
+    /// A CJK ideograph, kana, or Hangul syllable; each becomes its own
+    /// token.
+    Cjk,
+    /// An ASCII letter; runs of these are grouped into whole words. Accented
+    /// and other non-ASCII letters are [`CharType::Other`].
+    Latin,
+    /// An ASCII digit; runs of these are grouped into whole numbers.
+    Numeric,
+    /// Whitespace; ends the current run without becoming a token itself.
+    Whitespace,
+    /// Punctuation, symbols, or anything else; each becomes its own token.
+    Other,
+}
+
+/// Classify a single character into the categories [`auto_tokenize`] uses
+/// to decide where to split.
+#[must_use]
+pub fn get_char_type(c: char) -> CharType {
+    if c.is_whitespace() {
+        CharType::Whitespace
+    } else if c.is_ascii_digit() {
+        CharType::Numeric
+    } else if c.is_ascii_alphabetic() {
+        CharType::Latin
+    } else if is_cjk(c) {
+        CharType::Cjk
+    } else {
+        CharType::Other
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x309F   // Hiragana
+        | 0x30A0..=0x30FF // Katakana
+        | 0x3400..=0x4DBF // CJK Extension A
+        | 0x4E00..=0x9FFF // CJK Unified Ideographs
+        | 0xAC00..=0xD7A3 // Hangul syllables
+    )
+}
+
+/// A small built-in table of common long English words to their syllable
+/// breaks. It's nowhere near a complete hyphenation dictionary -- just
+/// enough to demonstrate the split -- but keeps the crate free of a
+/// bundled dictionary file and its parsing.
+fn hyphenation_dictionary() -> &'static HashMap<&'static str, &'static [&'static str]> {
+    static DICT: OnceLock<HashMap<&'static str, &'static [&'static str]>> = OnceLock::new();
+    DICT.get_or_init(|| {
+        HashMap::from([
+            ("hyphenation", &["hy", "phen", "a", "tion"][..]),
+            ("beautiful", &["beau", "ti", "ful"][..]),
+            ("wonderful", &["won", "der", "ful"][..]),
+            ("computer", &["com", "put", "er"][..]),
+            ("understand", &["un", "der", "stand"][..]),
+        ])
+    })
+}
+
+fn flush_run(run: &mut String, run_type: Option<CharType>, tokens: &mut Vec<String>) {
+    if run.is_empty() {
+        return;
+    }
+    if run_type == Some(CharType::Latin) {
+        if let Some(syllables) = hyphenation_dictionary().get(run.as_str()) {
+            tokens.extend(syllables.iter().map(|s| s.to_string()));
+            run.clear();
+            return;
+        }
+    }
+    tokens.push(std::mem::take(run));
+}
+
+/// Split `text` into tokens suitable for per-syllable timing.
+///
+/// # Examples
+///
+/// ```
+/// use ttml_core::auto_tokenize;
+///
+/// assert_eq!(
+///     auto_tokenize("hyphenation"),
+///     vec!["hy", "phen", "a", "tion"]
+/// );
+/// assert_eq!(auto_tokenize("hello world"), vec!["hello", "world"]);
+/// assert_eq!(auto_tokenize("日本語"), vec!["日", "本", "語"]);
+/// ```
+#[must_use]
+pub fn auto_tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut run = String::new();
+    let mut run_type: Option<CharType> = None;
+
+    for grapheme in text.graphemes(true) {
+        let char_type = match grapheme.chars().next() {
+            Some(c) => get_char_type(c),
+            None => continue,
+        };
+        match char_type {
+            CharType::Whitespace => {
+                flush_run(&mut run, run_type, &mut tokens);
+                run_type = None;
+            }
+            CharType::Latin | CharType::Numeric if run_type == Some(char_type) => {
+                run.push_str(grapheme);
+            }
+            CharType::Latin | CharType::Numeric => {
+                flush_run(&mut run, run_type, &mut tokens);
+                run.push_str(grapheme);
+                run_type = Some(char_type);
+            }
+            CharType::Cjk | CharType::Other => {
+                flush_run(&mut run, run_type, &mut tokens);
+                tokens.push(grapheme.to_string());
+                run_type = None;
+            }
+        }
+    }
+    flush_run(&mut run, run_type, &mut tokens);
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin_runs_are_kept_as_whole_words() {
+        assert_eq!(auto_tokenize("hello world"), vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn cjk_characters_are_split_one_at_a_time() {
+        assert_eq!(auto_tokenize("日本語"), vec!["日", "本", "語"]);
+    }
+
+    #[test]
+    fn numeric_runs_are_kept_whole_and_separate_from_letters() {
+        assert_eq!(auto_tokenize("track42"), vec!["track", "42"]);
+    }
+
+    #[test]
+    fn a_dictionary_word_is_split_at_its_hyphenation_points() {
+        assert_eq!(
+            auto_tokenize("hyphenation"),
+            vec!["hy", "phen", "a", "tion"]
+        );
+    }
+
+    #[test]
+    fn an_unrecognized_word_is_kept_whole() {
+        assert_eq!(
+            auto_tokenize("supercalifragilistic"),
+            vec!["supercalifragilistic"]
+        );
+    }
+
+    #[test]
+    fn punctuation_becomes_its_own_token() {
+        assert_eq!(auto_tokenize("hi!"), vec!["hi", "!"]);
+    }
+
+    #[test]
+    fn mixed_cjk_and_latin_text_splits_each_script_its_own_way() {
+        assert_eq!(auto_tokenize("你好world"), vec!["你", "好", "world"]);
+    }
+
+    #[test]
+    fn a_zwj_emoji_sequence_stays_a_single_token() {
+        let family_emoji = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(auto_tokenize(family_emoji), vec![family_emoji]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Core data types shared between the TTML parser and generator.
+
+use serde::{Deserialize, Serialize};
+
+/// A single word/syllable within a [`LyricLine`], carrying its own timing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    /// The syllable's own `xml:lang`, if it differs from the line's, for
+    /// mixed-language word-by-word lyrics (e.g. an English hook inside an
+    /// otherwise Japanese line) so a frontend can switch fonts per syllable.
+    pub lang: Option<String>,
+    /// Furigana readings for this word, populated by aligning it against a
+    /// kana/romaji reading track (see
+    /// [`align_furigana`](crate::align_furigana)) rather than by the parser
+    /// itself. `None` for a word with no furigana of its own.
+    pub furigana: Option<Vec<FuriganaSyllable>>,
+}
+
+/// One base-text/reading pair within a word's furigana, allowing a single
+/// word to carry more than one `<ruby>` if its kanji span multiple
+/// independently-read chunks (e.g. 取り消し -> と/り消/し).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FuriganaSyllable {
+    pub base: String,
+    pub reading: String,
+}
+
+/// Whether a line (or word) is the song's main vocal or a background vocal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentType {
+    #[default]
+    Main,
+    Background,
+}
+
+/// Whether an [`Agent`] is a single performer or a group (e.g. a chorus).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AgentType {
+    #[default]
+    Person,
+    Group,
+}
+
+/// A performer referenced by lyric lines via `ttm:agent`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Agent {
+    pub id: String,
+    pub name: Option<String>,
+    pub agent_type: AgentType,
+}
+
+/// A translation or romanization attached to a line, optionally tagged with
+/// the `xml:lang` it was written in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct AnnotatedText {
+    pub lang: Option<String>,
+    pub text: String,
+    /// The romanization system used (e.g. `"hepburn"`), from an inline
+    /// `x-roman` span's `xml:scheme` attribute. Always `None` for
+    /// translations.
+    pub scheme: Option<String>,
+    /// Per-syllable timing for a word-timed inline translation (an
+    /// `x-translation` span whose text came from nested per-syllable
+    /// `<span>`s rather than being written directly inside it). Empty for
+    /// every other kind of translation or romanization, in which case
+    /// `text` is the whole thing.
+    pub syllables: Vec<Word>,
+}
+
+/// The `itunes:song-part` value attached to a line, tracked separately for
+/// the enclosing `<div>` and the `<p>` itself so tools that re-segment lines
+/// can tell where a part boundary actually came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongPart {
+    pub div: Option<String>,
+    pub p: Option<String>,
+}
+
+impl SongPart {
+    /// The value used for grouping into `<div>`s when generating: the
+    /// `<p>`'s own value if it overrides the div, otherwise the div's.
+    pub fn effective(&self) -> Option<&str> {
+        self.p.as_deref().or(self.div.as_deref())
+    }
+}
+
+/// One `<p>` element worth of lyric content: a line, its words, and any
+/// translations/romanizations attached to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LyricLine {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// The line's single main-track syllables. A `<p>` has exactly one main
+    /// track by construction -- there's no second `words`-like field to put
+    /// a second original-language track in, so a `<p>` whose spans actually
+    /// hold two parallel main tracks (a bilingual original, e.g. a Japanese
+    /// lyric with its own Korean release text) has both flattened into this
+    /// one sequence in appearance order. [`crate::parse_ttml`] detects that
+    /// case -- a syllable starting earlier than one already seen, which a
+    /// single track's syllables never do -- and reports it as a warning
+    /// rather than silently producing a line whose words jump backwards in
+    /// time; the words themselves stay merged either way. A bilingual
+    /// original is better modeled as its own [`AnnotatedText`] in
+    /// `translations` with that language's `lang` set, so it round-trips
+    /// through [`crate::generate_ttml`] instead of being concatenated here.
+    pub words: Vec<Word>,
+    pub agent: Option<String>,
+    pub content_type: ContentType,
+    pub song_part: SongPart,
+    pub translations: Vec<AnnotatedText>,
+    pub romanizations: Vec<AnnotatedText>,
+    /// Background vocal syllables sharing this line (e.g. an "(oooh)"
+    /// ad-lib under the main lyric), from inline `ttm:role="x-bg"` spans.
+    /// Kept alongside `words` rather than as a separate [`LyricLine`] since
+    /// they share this line's timing window.
+    pub background: Vec<Word>,
+    /// The `itunes:key` identifying this line, used to attach head-level
+    /// translations (`<amll:translation for="...">`) to it by reference
+    /// instead of inline.
+    pub key: Option<String>,
+    /// The `<p region=...>` reference to a `<layout>` region defined in the
+    /// document's `<head>`, used by advanced TTML to position a line on
+    /// screen (e.g. top vs bottom for a duet). Kept and round-tripped as an
+    /// opaque string -- the `<head>` layout tree itself isn't parsed.
+    pub region: Option<String>,
+    /// Warnings produced while parsing this specific line, e.g. a lingering
+    /// `end` or a stray timestamp tag in a syllable's text. A subset of
+    /// [`ParsedSourceData::warnings`], kept here as well so a caller (e.g.
+    /// the bot rendering an inline review comment) can highlight the
+    /// offending line without re-parsing the message strings.
+    pub warnings: Vec<String>,
+}
+
+impl LyricLine {
+    pub fn new(start_ms: u64, end_ms: u64) -> Self {
+        Self {
+            start_ms,
+            end_ms,
+            words: Vec::new(),
+            agent: None,
+            content_type: ContentType::Main,
+            song_part: SongPart::default(),
+            translations: Vec::new(),
+            romanizations: Vec::new(),
+            background: Vec::new(),
+            key: None,
+            region: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The line's duration, saturating to zero rather than underflowing if
+    /// `end_ms` is somehow before `start_ms`.
+    #[must_use]
+    pub fn duration_ms(&self) -> u64 {
+        self.end_ms.saturating_sub(self.start_ms)
+    }
+
+    /// Drop every translation and romanization from the line, keeping its
+    /// main and background content untouched. For producing a "clean"
+    /// main-only export where a translation pack has since been retracted
+    /// or shouldn't ship with this particular file.
+    pub fn clear_annotations(&mut self) {
+        self.translations.clear();
+        self.romanizations.clear();
+    }
+}
+
+/// A single problem found while parsing or validating a TTML document, e.g.
+/// a line whose declared `end` lingers far past its last syllable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub message: String,
+}
+
+/// The fully parsed contents of a TTML document, ready for generation into
+/// another format or for validation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParsedSourceData {
+    pub lines: Vec<LyricLine>,
+    pub agents: Vec<Agent>,
+    pub metadata: Vec<(String, String)>,
+    pub warnings: Vec<String>,
+    /// Custom `<metadata>` children in a namespace this crate doesn't know
+    /// about (e.g. `<myns:bpm>120</myns:bpm>`), collected only when
+    /// [`TtmlParsingOptions::preserve_unknown_metadata`](crate::TtmlParsingOptions::preserve_unknown_metadata)
+    /// is set. Keyed by the element's qualified name (`"myns:bpm"`) so it
+    /// can be written back verbatim by the generator.
+    pub raw_metadata: Vec<(String, String)>,
+    /// The document root's own `itunes:timing` attribute, if present:
+    /// `Some(true)` for `"line"`/`"Line"`, `Some(false)` for
+    /// `"word"`/`"Word"` (matched case-insensitively), `None` if the
+    /// attribute is absent or unrecognized. Purely informational -- the
+    /// generator decides its own output timing mode per line from
+    /// [`LyricLine::words`] rather than trusting this back.
+    pub is_line_timing_mode: Option<bool>,
+}
+
+impl ParsedSourceData {
+    /// Total number of syllables/words across every line, for statistics
+    /// and validation that would otherwise flat-map `lines` themselves.
+    #[must_use]
+    pub fn syllable_count(&self) -> usize {
+        self.lines.iter().map(|line| line.words.len()).sum()
+    }
+
+    /// Resolve the document's main language, so callers don't each
+    /// re-implement the same fallback chain. In order of preference:
+    ///
+    /// 1. An explicit `language` entry in [`metadata`](Self::metadata)
+    ///    (e.g. written via `<amll:meta key="language" value="ja">`).
+    /// 2. A preserved `xml:lang` entry in [`raw_metadata`](Self::raw_metadata),
+    ///    for documents whose only language hint was on an element this
+    ///    crate doesn't otherwise interpret.
+    /// 3. The first per-syllable `xml:lang` found on any line's words, since
+    ///    a document where every syllable happens to carry the same tag is
+    ///    still unambiguous about what language it's in.
+    #[must_use]
+    pub fn main_language(&self) -> Option<&str> {
+        if let Some((_, value)) = self
+            .metadata
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("language"))
+        {
+            return Some(value.as_str());
+        }
+        if let Some((_, value)) = self
+            .raw_metadata
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("xml:lang"))
+        {
+            return Some(value.as_str());
+        }
+        self.lines
+            .iter()
+            .flat_map(|line| line.words.iter())
+            .find_map(|word| word.lang.as_deref())
+    }
+
+    /// The track's known duration in milliseconds, from a `durationMs` entry
+    /// in [`metadata`](Self::metadata) (e.g. written via
+    /// `<amll:meta key="durationMs" value="...">`), if one is present and
+    /// parses as an integer.
+    #[must_use]
+    pub fn expected_duration_ms(&self) -> Option<u64> {
+        self.metadata
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("durationMs"))
+            .and_then(|(_, value)| value.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_ms_subtracts_start_from_end() {
+        let line = LyricLine::new(1_000, 2_500);
+        assert_eq!(line.duration_ms(), 1_500);
+    }
+
+    #[test]
+    fn duration_ms_saturates_instead_of_underflowing() {
+        let line = LyricLine::new(2_000, 1_000);
+        assert_eq!(line.duration_ms(), 0);
+    }
+
+    #[test]
+    fn clear_annotations_drops_translations_and_romanizations_but_keeps_words() {
+        let mut line = LyricLine::new(0, 1_000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1_000,
+            text: "hi".into(),
+            lang: None,
+            furigana: None,
+        });
+        line.translations.push(AnnotatedText {
+            text: "hola".into(),
+            ..Default::default()
+        });
+        line.romanizations.push(AnnotatedText {
+            text: "hi".into(),
+            ..Default::default()
+        });
+        line.clear_annotations();
+        assert!(line.translations.is_empty());
+        assert!(line.romanizations.is_empty());
+        assert_eq!(line.words.len(), 1);
+    }
+
+    #[test]
+    fn syllable_count_sums_words_across_all_lines() {
+        let mut data = ParsedSourceData::default();
+        let mut a = LyricLine::new(0, 1000);
+        a.words.push(Word {
+            start_ms: 0,
+            end_ms: 500,
+            text: "a".into(),
+            lang: None,
+            furigana: None,
+        });
+        a.words.push(Word {
+            start_ms: 500,
+            end_ms: 1000,
+            text: "b".into(),
+            lang: None,
+            furigana: None,
+        });
+        let mut b = LyricLine::new(1000, 1500);
+        b.words.push(Word {
+            start_ms: 1000,
+            end_ms: 1500,
+            text: "c".into(),
+            lang: None,
+            furigana: None,
+        });
+        data.lines.push(a);
+        data.lines.push(b);
+        assert_eq!(data.syllable_count(), 3);
+    }
+
+    #[test]
+    fn main_language_prefers_an_explicit_metadata_entry() {
+        let mut data = ParsedSourceData::default();
+        data.metadata.push(("language".into(), "ja".into()));
+        data.raw_metadata.push(("xml:lang".into(), "en".into()));
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "a".into(),
+            lang: Some("ko".into()),
+            furigana: None,
+        });
+        data.lines.push(line);
+        assert_eq!(data.main_language(), Some("ja"));
+    }
+
+    #[test]
+    fn main_language_falls_back_to_raw_metadata_xml_lang() {
+        let mut data = ParsedSourceData::default();
+        data.raw_metadata.push(("xml:lang".into(), "en".into()));
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "a".into(),
+            lang: Some("ko".into()),
+            furigana: None,
+        });
+        data.lines.push(line);
+        assert_eq!(data.main_language(), Some("en"));
+    }
+
+    #[test]
+    fn main_language_falls_back_to_the_first_syllable_lang() {
+        let mut data = ParsedSourceData::default();
+        let mut line = LyricLine::new(0, 1000);
+        line.words.push(Word {
+            start_ms: 0,
+            end_ms: 1000,
+            text: "a".into(),
+            lang: Some("ko".into()),
+            furigana: None,
+        });
+        data.lines.push(line);
+        assert_eq!(data.main_language(), Some("ko"));
+    }
+
+    #[test]
+    fn main_language_is_none_with_no_hints_at_all() {
+        assert_eq!(ParsedSourceData::default().main_language(), None);
+    }
+
+    #[test]
+    fn syllable_count_is_zero_for_no_lines() {
+        assert_eq!(ParsedSourceData::default().syllable_count(), 0);
+    }
+
+    #[test]
+    fn expected_duration_ms_parses_a_durationms_metadata_entry() {
+        let mut data = ParsedSourceData::default();
+        data.metadata.push(("durationMs".into(), "245000".into()));
+        assert_eq!(data.expected_duration_ms(), Some(245_000));
+    }
+
+    #[test]
+    fn expected_duration_ms_is_none_without_it() {
+        assert_eq!(ParsedSourceData::default().expected_duration_ms(), None);
+    }
+}